@@ -1,516 +1,10131 @@
 use nu_plugin::Plugin;
 use nu_plugin::PluginCommand;
+use nu_protocol::engine::Closure;
 use nu_protocol::{
-    Category, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Type, Value,
+    Category, Filesize, FromValue, IntRange, LabeledError, ListStream, PipelineData, Record,
+    ShellError, Signature, Span, Spanned, SyntaxShape, Type, Value,
 };
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-use zip::{ExtraField, ZipArchive};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::{CompressionMethod, ExtraField, ZipArchive};
 
 pub struct UnzipPlugin;
 
 pub struct UnzipCommand;
 
-impl UnzipCommand {
-    fn list_files(
-        &self,
+pub struct UnzipListCommand;
+
+pub struct UnzipCleanCommand;
+
+/// How many bytes of a non-seekable archive are buffered in memory when `--max-size` isn't given
+const DEFAULT_MAX_BUFFER_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Errors of the extraction core, turned into a `LabeledError` once they reach the command
+enum UnzipError {
+    /// the archive file can't be opened
+    Open { reason: String, span: Span },
+    /// the archive isn't a readable ZIP file
+    Read { reason: String, span: Span },
+    /// the archive starts like a ZIP file but is cut off, like an incomplete download
+    Truncated { reason: String, span: Span },
+    /// a non-seekable archive is too large to be buffered
+    StreamTooLarge { max_size: u64, span: Span },
+    /// the data of an entry can't be decompressed
+    ReadEntry {
+        name: String,
+        error: std::io::Error,
         span: Span,
-        archive: &mut ZipArchive<std::fs::File>,
-    ) -> Result<PipelineData, LabeledError> {
-        let mut rows = Vec::new();
-        for i in 0..archive.len() {
-            if let Ok(file) = archive.by_index(i) {
-                let file_name = file.name();
-                let uncompressed_size = file.size();
-
-                let mut timestamp = None;
-                for field in file.extra_data_fields() {
-                    if let ExtraField::ExtendedTimestamp(timestamp_) = field {
-                        timestamp = timestamp_.mod_time();
-                        break;
-                    }
-                }
-                let last_modified: chrono::DateTime<chrono::Local> = match timestamp {
-                    Some(timestamp) => chrono::DateTime::from_timestamp(timestamp as i64, 0)
-                        .unwrap_or_default()
-                        .into(),
-                    None => {
-                        let zip_dt = file.last_modified().unwrap_or_default();
-                        let naive_dt: chrono::NaiveDateTime = zip_dt.try_into().unwrap_or_default();
-                        naive_dt
-                            .and_local_timezone(chrono::Local)
-                            .single()
-                            .unwrap_or_default()
-                    }
-                };
+    },
+    /// the data of an entry doesn't match its CRC32
+    Corrupt { name: String, span: Span },
+    /// a file would be overwritten without `--force`
+    Exists { path: PathBuf, span: Span },
+    /// a file or directory can't be created
+    Create {
+        path: PathBuf,
+        error: std::io::Error,
+        span: Span,
+    },
+    /// an extracted file can't be written
+    Write {
+        path: PathBuf,
+        error: std::io::Error,
+        span: Span,
+    },
+    /// an extracted file can't be moved into place with `--atomic`
+    Rename {
+        path: PathBuf,
+        error: std::io::Error,
+        span: Span,
+    },
+    /// the `--chmod` or `--chmod-dirs` mode can't be set
+    SetMode {
+        path: PathBuf,
+        error: std::io::Error,
+        span: Span,
+    },
+    /// the `--preserve-ownership` owner can't be set
+    SetOwner {
+        path: PathBuf,
+        error: std::io::Error,
+        span: Span,
+    },
+    /// more than `--max-size` bytes would be extracted
+    TooLarge { max_size: u64, span: Span },
+    /// `--timeout` ran out after extracting some entries
+    TimedOut { extracted: usize, span: Span },
+}
 
-                let mut row = Record::default();
-                row.push("name", Value::string(file_name, span));
-                row.push("size", Value::filesize(uncompressed_size as i64, span));
-                row.push("modified", Value::date(last_modified.into(), span));
+type UnzipResult<T> = Result<T, UnzipError>;
 
-                rows.push(Value::record(row, span));
-            }
+impl UnzipError {
+    /// Error reading the data of entry `name`, telling corrupted data from other failures
+    fn read_entry(name: String, error: std::io::Error, span: Span) -> Self {
+        // the zip crate's only hint of a CRC mismatch is the message
+        if error.kind() == std::io::ErrorKind::InvalidData
+            && error.to_string() == "Invalid checksum"
+        {
+            UnzipError::Corrupt { name, span }
+        } else {
+            UnzipError::ReadEntry { name, error, span }
         }
+    }
+}
 
-        Ok(PipelineData::Value(Value::list(rows, span), None))
+impl From<UnzipError> for LabeledError {
+    fn from(error: UnzipError) -> Self {
+        match error {
+            UnzipError::Open { reason, span } => {
+                LabeledError::new("Error opening ZIP file").with_label(reason, span)
+            }
+            UnzipError::Read { reason, span } => {
+                LabeledError::new("Error reading ZIP file").with_label(reason, span)
+            }
+            UnzipError::Truncated { reason, span } => {
+                LabeledError::new("ZIP file looks truncated, e.g. an incomplete download")
+                    .with_label(reason, span)
+            }
+            UnzipError::StreamTooLarge { max_size, span } => {
+                LabeledError::new(format!("ZIP stream is larger than {max_size} bytes"))
+                    .with_label("Use --max-size to raise the limit", span)
+            }
+            UnzipError::ReadEntry { name, error, span } => {
+                LabeledError::new(format!("Fail to read {name}"))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::Corrupt { name, span } => {
+                LabeledError::new(format!("Data corruption (CRC mismatch) in {name}"))
+                    .with_label("Use --skip-errors to unzip the other entries", span)
+            }
+            UnzipError::Exists { path, span } => {
+                LabeledError::new(format!("File {} already exists", path.display()))
+                    .with_label("Use --force/-f to overwrite", span)
+            }
+            UnzipError::Create { path, error, span } => {
+                LabeledError::new(format!("Fail to create {}", path.display()))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::Write { path, error, span } => {
+                LabeledError::new(format!("Fail to write {}", path.display()))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::Rename { path, error, span } => {
+                LabeledError::new(format!("Fail to rename to {}", path.display()))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::SetMode { path, error, span } => {
+                LabeledError::new(format!("Fail to set mode of {}", path.display()))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::SetOwner { path, error, span } => {
+                LabeledError::new(format!("Fail to set owner of {}", path.display()))
+                    .with_label(error.to_string(), span)
+            }
+            UnzipError::TooLarge { max_size, span } => {
+                LabeledError::new(format!("Extracted data is larger than {max_size} bytes"))
+                    .with_label("Use --max-size to raise the limit", span)
+            }
+            UnzipError::TimedOut { extracted, span } => LabeledError::new("Unzipping timed out")
+                .with_label(
+                    format!(
+                        "Aborted after {extracted} entries, the extracted files are incomplete"
+                    ),
+                    span,
+                ),
+        }
     }
+}
 
-    fn unzip_file(
-        &self,
+/// The data a ZIP archive is read from
+///
+/// `ZipArchive` needs to seek, so archives that can't be seeked (FIFOs, process substitution,
+/// character devices) are read into memory first.
+enum ArchiveSource {
+    File(std::fs::File),
+    Buffered(Cursor<Arc<[u8]>>),
+    /// an archive starting `offset` bytes into a file, see `--offset`
+    Embedded {
+        file: std::fs::File,
+        offset: u64,
+    },
+}
+
+/// Whether `path` can be read by `ZipArchive` directly, i.e. is a regular file
+fn is_seekable(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| m.is_file())
+}
+
+fn open_zip_file(path: &Path, span: Span) -> UnzipResult<std::fs::File> {
+    std::fs::File::open(path).map_err(|e| UnzipError::Open {
+        reason: e.to_string(),
+        span,
+    })
+}
+
+impl ArchiveSource {
+    fn open(
+        path: &Path,
+        max_buffer_size: u64,
+        offset: Option<u64>,
         span: Span,
-        archive: &mut ZipArchive<std::fs::File>,
-        force: bool,
-        debug: bool,
-        dir: &Path,
-    ) -> Result<PipelineData, LabeledError> {
-        for i in 0..archive.len() {
-            if let Ok(mut file) = archive.by_index(i) {
-                let out_path = match file.enclosed_name() {
-                    Some(path) => dir.join(path),
-                    None => continue,
-                };
+    ) -> UnzipResult<Self> {
+        let mut file = open_zip_file(path, span)?;
+        if let Some(offset) = offset {
+            let open_error = |reason| UnzipError::Open { reason, span };
+            if !is_seekable(path) {
+                return Err(open_error("--offset needs a regular file".to_string()));
+            }
+            let len = file
+                .metadata()
+                .map_err(|e| open_error(e.to_string()))?
+                .len();
+            if offset > len {
+                return Err(open_error(format!(
+                    "offset {offset} is beyond the end of the file, at {len}"
+                )));
+            }
+            file.seek(SeekFrom::Start(offset))
+                .map_err(|e| open_error(e.to_string()))?;
+            return Ok(ArchiveSource::Embedded { file, offset });
+        }
+        if is_seekable(path) {
+            return Ok(ArchiveSource::File(file));
+        }
 
-                if debug {
-                    eprintln!("Extracting {}", out_path.display());
-                }
+        let mut buffer = Vec::new();
+        (&mut file)
+            .take(max_buffer_size.saturating_add(1))
+            .read_to_end(&mut buffer)
+            .map_err(|e| UnzipError::Read {
+                reason: e.to_string(),
+                span,
+            })?;
+        if buffer.len() as u64 > max_buffer_size {
+            return Err(UnzipError::StreamTooLarge {
+                max_size: max_buffer_size,
+                span,
+            });
+        }
 
-                if out_path.exists() && !force {
-                    return Err(LabeledError::new(format!(
-                        "File {} already exists",
-                        out_path.to_string_lossy()
-                    ))
-                    .with_label("Use --force/-f to overwrite", span));
-                }
+        Ok(ArchiveSource::Buffered(Cursor::new(buffer.into())))
+    }
 
-                if file.is_dir() {
-                    std::fs::create_dir_all(&out_path).map_err(|e| {
-                        let out_dir = out_path.to_string_lossy();
-                        LabeledError::new(format!("Fail to create {out_dir}"))
-                            .with_label(e.to_string(), span)
-                    })?;                    
-                } else {
-                    // are all directories already created ?
-                    if let Some(out_dir) = out_path.parent() {
-                        std::fs::create_dir_all(out_dir).map_err(|e| {
-                            let out_dir = out_dir.to_string_lossy();
-                            LabeledError::new(format!("Fail to create {out_dir}"))
-                                .with_label(e.to_string(), span)
-                        })?;
-                    }
+    /// An archive given as bytes with `--data`
+    fn from_data(data: &Arc<[u8]>, offset: Option<u64>, span: Span) -> UnzipResult<Self> {
+        match offset {
+            Some(offset) if offset > data.len() as u64 => Err(UnzipError::Open {
+                reason: format!(
+                    "offset {offset} is beyond the end of the data, at {}",
+                    data.len()
+                ),
+                span,
+            }),
+            Some(offset) => Ok(ArchiveSource::Buffered(Cursor::new(
+                data[offset as usize..].into(),
+            ))),
+            None => Ok(ArchiveSource::Buffered(Cursor::new(data.clone()))),
+        }
+    }
 
-                    let mut output_file =
-                        std::io::BufWriter::new(std::fs::File::create(&out_path).map_err(|e| {
-                            let out_path = out_path.to_string_lossy();
-                            LabeledError::new(format!("Fail to create {out_path}"))
-                                .with_label(e.to_string(), span)
-                        })?);
-                    let mut buffer = [0; 1024];
-                    loop {
-                        let bytes_read = file.read(&mut buffer).map_err(|e| {
-                            let file_name = file.name();
-                            LabeledError::new(format!("Fail to read {file_name}"))
-                                .with_label(e.to_string(), span)
-                        })?;
-                        if bytes_read == 0 {
-                            break;
-                        }
-                        output_file.write_all(&buffer[0..bytes_read]).map_err(|e| {
-                            let out_path = out_path.to_string_lossy();
-                            LabeledError::new(format!("Fail to write {out_path}"))
-                                .with_label(e.to_string(), span)
-                        })?;
-                    }
-                }
+    /// A second handle to the same data, used to read headers `ZipArchive` doesn't expose
+    ///
+    /// Cloned files share their position, so every read has to seek first.
+    fn try_clone(&self, span: Span) -> UnzipResult<Self> {
+        match self {
+            ArchiveSource::File(file) => {
+                file.try_clone()
+                    .map(ArchiveSource::File)
+                    .map_err(|e| UnzipError::Open {
+                        reason: e.to_string(),
+                        span,
+                    })
             }
+            ArchiveSource::Buffered(cursor) => Ok(ArchiveSource::Buffered(cursor.clone())),
+            ArchiveSource::Embedded { file, offset } => file
+                .try_clone()
+                .map(|file| ArchiveSource::Embedded {
+                    file,
+                    offset: *offset,
+                })
+                .map_err(|e| UnzipError::Open {
+                    reason: e.to_string(),
+                    span,
+                }),
+        }
+    }
+}
+
+fn read_archive(source: ArchiveSource, span: Span) -> UnzipResult<ZipArchive<ArchiveSource>> {
+    let mut raw = source.try_clone(span)?;
+    ZipArchive::new(source).map_err(|e| {
+        let reason = e.to_string();
+        if starts_like_zip(&mut raw) {
+            UnzipError::Truncated { reason, span }
+        } else {
+            UnzipError::Read { reason, span }
         }
+    })
+}
 
-        Ok(PipelineData::Value(Value::nothing(span), None))
+/// Whether `reader` starts with a local file header, as an archive cut before its central
+/// directory does
+fn starts_like_zip(reader: &mut (impl Read + Seek)) -> bool {
+    let mut signature = [0; 4];
+    reader.seek(SeekFrom::Start(0)).is_ok()
+        && reader.read_exact(&mut signature).is_ok()
+        && u32::from_le_bytes(signature) == LocalHeader::SIGNATURE
+}
+
+/// Whether an entry of `archive` has data past the `len` bytes the archive has
+fn references_beyond<R: Read + Seek>(archive: &mut ZipArchive<R>, len: u64) -> bool {
+    (0..archive.len()).any(|i| {
+        archive
+            .by_index_raw(i)
+            .is_ok_and(|file| file.data_start() + file.compressed_size() > len)
+    })
+}
+
+/// Names and local header offsets of central directory entries that a later entry with the
+/// same name replaces, `ZipArchive` only exposes the last one
+fn superseded_entries(
+    reader: &mut (impl Read + Seek),
+    dir_start: u64,
+) -> std::io::Result<Vec<(String, u64)>> {
+    let mut entries = Vec::new();
+    let mut offset = dir_start;
+    // the central directory ends where the headers stop, at the end of central directory record,
+    // which is shorter than a header without a comment
+    loop {
+        let header = match CentralHeader::read(reader, offset) {
+            Ok(header) => header,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        };
+        let mut name = vec![0; header.name_len as usize];
+        reader.read_exact(&mut name)?;
+        entries.push((
+            String::from_utf8_lossy(&name).into_owned(),
+            header.header_offset as u64,
+        ));
+        offset = header.next_offset(offset);
+    }
+
+    let mut last = HashMap::new();
+    for (i, (name, _)) in entries.iter().enumerate() {
+        last.insert(name.clone(), i);
     }
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (name, _))| last[name] != *i)
+        .map(|(_, entry)| entry)
+        .collect())
 }
 
-impl PluginCommand for UnzipCommand {
-    type Plugin = UnzipPlugin;
+/// Fields of an entry's central directory header that `ZipFile` doesn't expose
+#[derive(Default)]
+struct CentralHeader {
+    version_made_by: u16,
+    version_needed: u16,
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    name_len: u16,
+    extra_len: u16,
+    comment_len: u16,
+    external_attributes: u32,
+    header_offset: u32,
+}
 
-    fn name(&self) -> &str {
-        "unzip"
+impl CentralHeader {
+    const SIGNATURE: u32 = 0x02014b50;
+    const SIZE: usize = 46;
+
+    /// Read the fixed size part of the header at `offset`, see
+    /// [`ZipFile::central_header_start`]
+    fn read(reader: &mut (impl Read + Seek), offset: u64) -> std::io::Result<Self> {
+        let mut block = [0; Self::SIZE];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut block)?;
+
+        let u16_at = |i: usize| u16::from_le_bytes([block[i], block[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([block[i], block[i + 1], block[i + 2], block[i + 3]]);
+        if u32_at(0) != Self::SIGNATURE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid central directory header signature",
+            ));
+        }
+
+        Ok(Self {
+            version_made_by: u16_at(4),
+            version_needed: u16_at(6),
+            flags: u16_at(8),
+            method: u16_at(10),
+            crc32: u32_at(16),
+            compressed_size: u32_at(20),
+            uncompressed_size: u32_at(24),
+            name_len: u16_at(28),
+            extra_len: u16_at(30),
+            comment_len: u16_at(32),
+            external_attributes: u32_at(38),
+            header_offset: u32_at(42),
+        })
     }
 
-    fn signature(&self) -> Signature {
-        Signature::build("unzip")
-            .switch(
-                "list",
-                "list files in zip file, return table<name, size, modified>",
-                Some('l'),
-            )
-            .switch("force", "force overwrite", Some('f'))
-            .switch("debug", "print debug information", None)
-            .named(
-                "dir",
-                SyntaxShape::Directory,
-                "the directory to unzip to, default current directory",
-                Some('d'),
-            )
-            .required("file", SyntaxShape::Filepath, "the file to unzip")
-            .input_output_types(vec![
-                (
-                    Type::Nothing,
-                    Type::Table(Box::new([
-                        ("name".into(), Type::String),
-                        ("size".into(), Type::Filesize),
-                        ("modified".into(), Type::Date),
-                    ])),
-                ),
-                (Type::Nothing, Type::Nothing),
-            ])
-            .allow_variants_without_examples(true)
-            .category(Category::FileSystem)
-            .filter()
+    /// Offset of the header following this one at `offset`
+    fn next_offset(&self, offset: u64) -> u64 {
+        offset
+            + Self::SIZE as u64
+            + self.name_len as u64
+            + self.extra_len as u64
+            + self.comment_len as u64
     }
 
-    fn description(&self) -> &str {
-        "unzip file"
+    /// Fields the local header of the entry disagrees on, which may indicate tampering
+    fn mismatches(&self, local: &LocalHeader, name_raw: &[u8]) -> Vec<&'static str> {
+        let mut mismatches = Vec::new();
+        if local.method != self.method {
+            mismatches.push("compression method");
+        }
+        if local.name != name_raw {
+            mismatches.push("name");
+        }
+        // with a data descriptor, CRC and sizes follow the data instead
+        if local.flags & 0x08 == 0 {
+            if local.crc32 != self.crc32 {
+                mismatches.push("crc32");
+            }
+            // sizes of ZIP64 entries are in the extra field
+            let zip64 = |size| size == u32::MAX;
+            if !zip64(local.compressed_size)
+                && !zip64(self.compressed_size)
+                && local.compressed_size != self.compressed_size
+            {
+                mismatches.push("compressed size");
+            }
+            if !zip64(local.uncompressed_size)
+                && !zip64(self.uncompressed_size)
+                && local.uncompressed_size != self.uncompressed_size
+            {
+                mismatches.push("size");
+            }
+        }
+        mismatches
     }
 
-    fn run(
-        &self,
-        _plugin: &Self::Plugin,
-        engine: &nu_plugin::EngineInterface,
-        call: &nu_plugin::EvaluatedCall,
-        _input: PipelineData,
-    ) -> Result<PipelineData, LabeledError> {
-        let zip_file_path = call.req::<PathBuf>(0)?;
-        let zip_file_path = if zip_file_path.is_relative() {
-            let current_dir = std::path::PathBuf::from(engine.get_current_dir()?);
-            current_dir.join(zip_file_path)
-        } else {
-            zip_file_path
+    /// Version of the ZIP specification the entry was made with, like "6.3"
+    fn made_by_version(&self) -> String {
+        let version = self.version_made_by & 0xff;
+        format!("{}.{}", version / 10, version % 10)
+    }
+
+    /// Version of the ZIP specification needed to extract the entry, with the feature
+    /// requiring it if known, like "4.5 (zip64)"
+    fn version_needed(&self) -> String {
+        let version = self.version_needed & 0xff;
+        let feature = match version {
+            21 => "deflate64",
+            45 => "zip64",
+            46 => "bzip2",
+            51 => "aes",
+            63 => "lzma",
+            _ => return format!("{}.{}", version / 10, version % 10),
         };
+        format!("{}.{} ({feature})", version / 10, version % 10)
+    }
 
-        let zip_file = std::fs::File::open(zip_file_path).map_err(|e| {
-            LabeledError::new("Error opening ZIP file").with_label(e.to_string(), call.head)
-        })?;
+    /// The general purpose bit flag as hex, with the names of the known bits that are set
+    fn flags(&self, span: Span) -> Value {
+        let names = [
+            (0, "encrypted"),
+            (3, "data_descriptor"),
+            (5, "patched"),
+            (6, "strong_encryption"),
+            (11, "utf8"),
+            (13, "masked_headers"),
+        ]
+        .into_iter()
+        .filter(|(bit, _)| self.flags & (1 << bit) != 0)
+        .map(|(_, name)| Value::string(name, span))
+        .collect();
+        let mut record = Record::default();
+        record.push("raw", Value::string(format!("{:#06x}", self.flags), span));
+        record.push("names", Value::list(names, span));
+        Value::record(record, span)
+    }
 
-        let mut archive = ZipArchive::new(zip_file).map_err(|e| {
-            LabeledError::new("Error reading ZIP file").with_label(e.to_string(), call.head)
-        })?;
+    /// Names of the DOS attribute bits set in the low word of the external attributes
+    fn dos_attributes(&self) -> Vec<&'static str> {
+        [
+            (0x01, "readonly"),
+            (0x02, "hidden"),
+            (0x04, "system"),
+            (0x20, "archive"),
+            (0x400, "reparse_point"),
+        ]
+        .into_iter()
+        .filter(|(bit, _)| self.external_attributes & bit != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
 
-        let list_only = call.has_flag("list")?;
-        if list_only {
-            self.list_files(call.head, &mut archive)
-        } else {
-            let force = call.has_flag("force")?;
-            let debug = call.has_flag("debug")?;
-
-            let current_dir: PathBuf = engine.get_current_dir()?.into();
-            let dir = call
-                .get_flag::<PathBuf>("dir")?
-                .map(|p| {
-                    if p.is_relative() {
-                        current_dir.join(p)
-                    } else {
-                        p
-                    }
-                })
-                .unwrap_or_else(|| current_dir);
-            self.unzip_file(call.head, &mut archive, force, debug, &dir)
+    /// Whether Windows hides the entry, see `--skip-hidden`
+    fn is_hidden(&self) -> bool {
+        self.external_attributes & 0x06 != 0
+    }
+
+    /// Whether the entry was a reparse point, like a junction or symlink, see `--allow-reparse`
+    fn is_reparse_point(&self) -> bool {
+        self.external_attributes & 0x400 != 0
+    }
+
+    /// Operating system the entry was made on, as encoded in the high byte of "version made by"
+    fn host_os(&self) -> String {
+        let os = match self.version_made_by >> 8 {
+            0 => "fat",
+            1 => "amiga",
+            2 => "openvms",
+            3 => "unix",
+            4 => "vm/cms",
+            5 => "atari",
+            6 => "hpfs",
+            7 => "macintosh",
+            8 => "z-system",
+            9 => "cp/m",
+            10 => "ntfs",
+            11 => "mvs",
+            12 => "vse",
+            13 => "acorn",
+            14 => "vfat",
+            15 => "alternate mvs",
+            16 => "beos",
+            17 => "tandem",
+            18 => "os/400",
+            19 => "osx",
+            n => return format!("unknown({n})"),
+        };
+        os.to_string()
+    }
+}
+
+/// Indexes of the entries whose central directory header in `raw` matches `flagged`
+fn flagged_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    raw: &mut (impl Read + Seek),
+    flagged: impl Fn(&CentralHeader) -> bool,
+) -> HashSet<usize> {
+    (0..archive.len())
+        .filter(|&i| {
+            archive.by_index_raw(i).is_ok_and(|file| {
+                CentralHeader::read(raw, file.central_header_start())
+                    .is_ok_and(|header| flagged(&header))
+            })
+        })
+        .collect()
+}
+
+/// Fields of an entry's local header, to compare with its central directory header
+struct LocalHeader {
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    name: Vec<u8>,
+}
+
+impl LocalHeader {
+    const SIGNATURE: u32 = 0x04034b50;
+    const SIZE: usize = 30;
+
+    /// Read the header and name at `offset`, see [`ZipFile::header_start`]
+    fn read(reader: &mut (impl Read + Seek), offset: u64) -> std::io::Result<Self> {
+        let mut block = [0; Self::SIZE];
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut block)?;
+
+        let u16_at = |i: usize| u16::from_le_bytes([block[i], block[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_le_bytes([block[i], block[i + 1], block[i + 2], block[i + 3]]);
+        if u32_at(0) != Self::SIGNATURE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid local file header signature",
+            ));
         }
+
+        let mut name = vec![0; u16_at(26) as usize];
+        reader.read_exact(&mut name)?;
+        Ok(Self {
+            flags: u16_at(6),
+            method: u16_at(8),
+            crc32: u32_at(14),
+            compressed_size: u32_at(18),
+            uncompressed_size: u32_at(22),
+            name,
+        })
     }
 }
 
-impl Plugin for UnzipPlugin {
-    fn version(&self) -> String {
-        env!("CARGO_PKG_VERSION").into()
+impl Read for ArchiveSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ArchiveSource::File(file) | ArchiveSource::Embedded { file, .. } => file.read(buf),
+            ArchiveSource::Buffered(cursor) => cursor.read(buf),
+        }
     }
+}
 
-    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
-        vec![Box::new(UnzipCommand)]
+impl Seek for ArchiveSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            ArchiveSource::File(file) => file.seek(pos),
+            ArchiveSource::Buffered(cursor) => cursor.seek(pos),
+            // positions are relative to the start of the archive, and can't go before it
+            ArchiveSource::Embedded { file, offset } => {
+                let pos = match pos {
+                    SeekFrom::Start(pos) => SeekFrom::Start(*offset + pos),
+                    pos => pos,
+                };
+                let current = file.stream_position()?;
+                let position = file.seek(pos)?;
+                if position < *offset {
+                    file.seek(SeekFrom::Start(current))?;
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "seek before the start of the archive",
+                    ));
+                }
+                Ok(position - *offset)
+            }
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::Result;
-    use chrono::{DateTime, Local};
-    use nu_plugin_test_support::PluginTest;
-    use nu_protocol::{IntoValue, Record, Value};
-    use std::fs;
-    use std::fs::File;
+/// Which kind of entries `--list` should report
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    All,
+    Files,
+    Dirs,
+}
 
-    fn make_plugin_with_pwd(pwd: &Path) -> Result<PluginTest> {
-        let mut plugin = PluginTest::new("unzip", UnzipPlugin.into())?;
+impl EntryKind {
+    fn matches(self, is_dir: bool) -> bool {
+        match self {
+            EntryKind::All => true,
+            EntryKind::Files => !is_dir,
+            EntryKind::Dirs => is_dir,
+        }
+    }
+}
 
-        let pwd = Value::string(pwd.to_string_lossy(), Span::test_data());
-        plugin
-            .engine_state_mut()
-            .add_env_var("PWD".to_string(), pwd);
+/// Compression of the tar file written by `--to-tar`, `--to-tar-gz` or `--to-tar-zst`
+#[derive(Clone, Copy)]
+enum TarCompression {
+    None,
+    Gzip,
+    Zstd,
+}
 
-        Ok(plugin)
+impl TarCompression {
+    const FLAGS: [(&'static str, TarCompression); 3] = [
+        ("to-tar", TarCompression::None),
+        ("to-tar-gz", TarCompression::Gzip),
+        ("to-tar-zst", TarCompression::Zstd),
+    ];
+
+    /// Check `--level`, returning the default level without it
+    fn level(self, level: Option<i32>, span: Span) -> Result<i32, LabeledError> {
+        let (range, default) = match self {
+            TarCompression::None if level.is_some() => {
+                return Err(LabeledError::new("Invalid level")
+                    .with_label("--level needs --to-tar-gz or --to-tar-zst", span))
+            }
+            TarCompression::None => return Ok(0),
+            TarCompression::Gzip => (0..=9, 6),
+            TarCompression::Zstd => (1..=22, 3),
+        };
+        match level {
+            Some(level) if !range.contains(&level) => Err(LabeledError::new(format!(
+                "Invalid level {level}"
+            ))
+            .with_label(
+                format!("Expected a level from {} to {}", range.start(), range.end()),
+                span,
+            )),
+            level => Ok(level.unwrap_or(default)),
+        }
     }
+}
 
-    fn make_plugin() -> Result<PluginTest> {
-        make_plugin_with_pwd(std::env::temp_dir().as_path())
+/// Separator used in the names `--list` reports, see `--path-style`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PathStyle {
+    Unix,
+    Windows,
+}
+
+impl PathStyle {
+    fn parse(style: &str, span: Span) -> Result<Self, LabeledError> {
+        match style {
+            "native" if cfg!(windows) => Ok(PathStyle::Windows),
+            "native" | "unix" => Ok(PathStyle::Unix),
+            "windows" => Ok(PathStyle::Windows),
+            _ => Err(LabeledError::new(format!("Invalid path style {style}"))
+                .with_label("Expected native, unix or windows", span)),
+        }
     }
 
-    // Get the current time
-    // convert to zip datetime and back, so that time is truncated as zip datetime
-    fn now() -> DateTime<Local> {
-        let t = Local::now();
-        let zt = zip::DateTime::try_from(t.naive_local()).unwrap();
-        let naive_dt: chrono::NaiveDateTime = zt.try_into().unwrap_or_default();
-        naive_dt
-            .and_local_timezone(Local)
-            .single()
-            .unwrap_or_default()
+    fn display(self, name: &str) -> String {
+        match self {
+            PathStyle::Unix => name.replace('\\', "/"),
+            PathStyle::Windows => name.replace('/', "\\"),
+        }
     }
+}
 
-    struct TempZipFile {
-        _path: PathBuf,
+/// Timestamp `--list` reports as modified, see `--time-source`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimeSource {
+    Extended,
+    Ntfs,
+    Dos,
+    /// the most recent of all timestamps the entry has
+    Newest,
+}
+
+impl TimeSource {
+    fn parse(source: &str, span: Span) -> Result<Self, LabeledError> {
+        match source {
+            "extended" => Ok(TimeSource::Extended),
+            "ntfs" => Ok(TimeSource::Ntfs),
+            "dos" => Ok(TimeSource::Dos),
+            "newest" => Ok(TimeSource::Newest),
+            _ => Err(LabeledError::new(format!("Invalid time source {source}"))
+                .with_label("Expected extended, ntfs, dos or newest", span)),
+        }
+    }
+
+    /// Modification time of `file` from this source, or its DOS time if it lacks it
+    fn modified(self, file: &ZipFile) -> chrono::DateTime<chrono::Local> {
+        // the DOS time is always there, don't parse extra fields for it
+        if self == TimeSource::Dos {
+            return dos_modified(file);
+        }
+        let (ntfs, extended) = extra_timestamps(file);
+        match self {
+            TimeSource::Extended => extended.map(Into::into),
+            TimeSource::Ntfs => ntfs.map(Into::into),
+            TimeSource::Dos => None,
+            TimeSource::Newest => {
+                let dos = dos_modified(file);
+                return [ntfs, extended]
+                    .into_iter()
+                    .flatten()
+                    .map(chrono::DateTime::<chrono::Local>::from)
+                    .fold(dos, |newest, timestamp| newest.max(timestamp));
+            }
+        }
+        .unwrap_or_else(|| dos_modified(file))
+    }
+}
+
+/// Reports user facing messages of a command run in one consistent format
+///
+/// Warnings go to stderr unless `--quiet` is given, and are also kept so that callers (and
+/// tests) can inspect what was reported.
+#[derive(Default)]
+struct Log {
+    quiet: bool,
+    debug: bool,
+    warnings: Vec<String>,
+}
+
+impl Log {
+    fn warn(&mut self, msg: impl Into<String>) {
+        let msg = msg.into();
+        if !self.quiet {
+            eprintln!("warning: {msg}");
+        }
+        self.warnings.push(msg);
+    }
+
+    fn debug(&self, msg: impl AsRef<str>) {
+        if self.debug {
+            eprintln!("{}", msg.as_ref());
+        }
+    }
+}
+
+/// Defaults read from the plugin configuration, `$env.config.plugins.unzip`
+///
+/// The configuration is a record with any of the keys `force`, `quiet`, `debug` (bools),
+/// `dir` (string) and `max_size` (filesize). Flags given on the command line take precedence.
+#[derive(Default)]
+struct PluginConfig {
+    force: bool,
+    quiet: bool,
+    debug: bool,
+    dir: Option<PathBuf>,
+    max_size: Option<u64>,
+}
+
+impl PluginConfig {
+    fn from_value(value: Option<Value>) -> Result<Self, LabeledError> {
+        let mut config = PluginConfig::default();
+        let Some(value) = value else {
+            return Ok(config);
+        };
+
+        for (key, value) in value.as_record()?.iter() {
+            match key.as_str() {
+                "force" => config.force = value.as_bool()?,
+                "quiet" => config.quiet = value.as_bool()?,
+                "debug" => config.debug = value.as_bool()?,
+                "dir" => config.dir = Some(PathBuf::from(value.as_str()?)),
+                "max_size" => config.max_size = Some(value.as_filesize()?.get().max(0) as u64),
+                _ => {
+                    return Err(
+                        LabeledError::new(format!("Unknown unzip config option {key}")).with_label(
+                            "expected one of force, quiet, debug, dir or max_size",
+                            value.span(),
+                        ),
+                    )
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Compression methods counted by `--stats`, anything else is counted as "other"
+const STATS_METHODS: [(&str, CompressionMethod); 7] = [
+    ("stored", CompressionMethod::STORE),
+    ("deflated", CompressionMethod::DEFLATE),
+    ("deflate64", CompressionMethod::DEFLATE64),
+    ("bzip2", CompressionMethod::BZIP2),
+    ("zstd", CompressionMethod::ZSTD),
+    ("lzma", CompressionMethod::LZMA),
+    ("xz", CompressionMethod::XZ),
+];
+
+/// Lowercase name of a compression method, as in the `--stats` columns
+fn method_name(method: CompressionMethod) -> String {
+    match STATS_METHODS.iter().find(|(_, known)| *known == method) {
+        Some((name, _)) => name.to_string(),
+        None => method.to_string().to_lowercase(),
+    }
+}
+
+/// Compression figures aggregated over all entries for `--stats`
+#[derive(Default)]
+struct CompressionStats {
+    entries: u64,
+    /// entries per method of `STATS_METHODS`, followed by the other methods
+    methods: [u64; STATS_METHODS.len() + 1],
+    total_uncompressed: u64,
+    total_compressed: u64,
+    /// an archive couldn't be read
+    invalid: bool,
+    /// an archive seems cut off, lacking its end or data its central directory refers to
+    truncated: bool,
+}
+
+impl CompressionStats {
+    fn add(&mut self, file: &ZipFile) {
+        let method = STATS_METHODS
+            .iter()
+            .position(|(_, method)| *method == file.compression())
+            .unwrap_or(STATS_METHODS.len());
+        self.entries += 1;
+        self.methods[method] += 1;
+        self.total_uncompressed += file.size();
+        self.total_compressed += file.compressed_size();
+    }
+
+    fn into_value(self, span: Span) -> Value {
+        let mut record = Record::default();
+        record.push("entries", Value::int(self.entries as i64, span));
+        let names = STATS_METHODS.iter().map(|(name, _)| *name).chain(["other"]);
+        for (name, count) in names.zip(self.methods) {
+            record.push(name, Value::int(count as i64, span));
+        }
+        record.push(
+            "total_uncompressed",
+            Value::filesize(self.total_uncompressed as i64, span),
+        );
+        record.push(
+            "total_compressed",
+            Value::filesize(self.total_compressed as i64, span),
+        );
+        // how many times smaller the entries are compressed, 1.0 for an empty archive
+        let ratio = if self.total_compressed == 0 {
+            1.0
+        } else {
+            self.total_uncompressed as f64 / self.total_compressed as f64
+        };
+        record.push("overall_ratio", Value::float(ratio, span));
+        record.push("valid", Value::bool(!self.invalid, span));
+        record.push("truncated", Value::bool(self.truncated, span));
+        Value::record(record, span)
+    }
+}
+
+/// A directory of the `--to-json` and `--nested` trees
+#[derive(Default)]
+struct ManifestDir {
+    dirs: Vec<(String, ManifestDir)>,
+    files: Vec<Value>,
+}
+
+impl ManifestDir {
+    /// Add a file record at `path`, or just the directory for `None`, creating the
+    /// directories leading to it
+    fn insert(&mut self, path: &str, mut file: Option<Value>) {
+        let mut dir = self;
+        let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+        while let Some(component) = components.next() {
+            if components.peek().is_none() {
+                if let Some(file) = file.take() {
+                    dir.files.push(file);
+                    return;
+                }
+            }
+            let position = match dir.dirs.iter().position(|(name, _)| name == component) {
+                Some(position) => position,
+                None => {
+                    dir.dirs
+                        .push((component.to_string(), ManifestDir::default()));
+                    dir.dirs.len() - 1
+                }
+            };
+            dir = &mut dir.dirs[position].1;
+        }
+    }
+
+    /// Directories come first, then files, each in archive order
+    fn into_children(self, span: Span) -> Value {
+        let dirs = self.dirs.into_iter().map(|(name, dir)| {
+            let mut record = Record::default();
+            record.push("name", Value::string(name, span));
+            record.push("type", Value::string("dir", span));
+            record.push("children", dir.into_children(span));
+            Value::record(record, span)
+        });
+        Value::list(dirs.chain(self.files).collect(), span)
+    }
+}
+
+/// How many leading bytes of each entry `--verify-text` looks at
+const TEXT_SNIFF_SIZE: usize = 8192;
+
+/// The first `size` bytes of an entry as text for `--preview`, `<binary>` if they aren't text
+fn preview(reader: impl Read, size: usize, span: Span) -> std::io::Result<Value> {
+    let mut prefix = Vec::with_capacity(size);
+    reader.take(size as u64).read_to_end(&mut prefix)?;
+    let text = match std::str::from_utf8(&prefix) {
+        Ok(text) if !text.contains('\0') => text,
+        // a character cut at the end of the prefix
+        Err(e) if e.error_len().is_none() && !prefix.contains(&0) => {
+            std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap_or_default()
+        }
+        _ => "<binary>",
+    };
+    Ok(Value::string(text, span))
+}
+
+/// Length of the UTF-8 or UTF-16 byte order mark `prefix` starts with, 0 if there is none or
+/// the rest of `prefix` isn't text in that encoding
+fn text_bom_len(prefix: &[u8]) -> usize {
+    if let Some(text) = prefix.strip_prefix(b"\xef\xbb\xbf") {
+        // a character may be cut at the end of the prefix
+        let valid = match std::str::from_utf8(text) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        };
+        return if valid && !text.contains(&0) { 3 } else { 0 };
+    }
+    for (bom, big_endian) in [(b"\xff\xfe", false), (b"\xfe\xff", true)] {
+        let Some(text) = prefix.strip_prefix(bom) else {
+            continue;
+        };
+        let units = text.chunks_exact(2).map(|pair| {
+            if big_endian {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_le_bytes([pair[0], pair[1]])
+            }
+        });
+        let chars: Vec<_> = char::decode_utf16(units).collect();
+        // as for UTF-8, the last surrogate pair may be cut, and a UTF-32 little endian BOM
+        // also starts with FF FE, followed by a NUL
+        let valid = chars.iter().enumerate().all(|(i, c)| match c {
+            Ok(c) => *c != '\0',
+            Err(_) => i + 1 == chars.len(),
+        });
+        return if valid { 2 } else { 0 };
+    }
+    0
+}
+
+/// Line endings used in `prefix`: lf, crlf, cr, mixed, none, or binary if it has NUL bytes
+fn line_ending(prefix: &[u8]) -> &'static str {
+    if prefix.contains(&0) {
+        return "binary";
+    }
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut bytes = prefix.iter().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\r' if bytes.peek() == Some(&&b'\n') => {
+                bytes.next();
+                crlf += 1;
+            }
+            b'\r' => cr += 1,
+            b'\n' => lf += 1,
+            _ => {}
+        }
+    }
+    match (lf > 0, crlf > 0, cr > 0) {
+        (false, false, false) => "none",
+        (true, false, false) => "lf",
+        (false, true, false) => "crlf",
+        (false, false, true) => "cr",
+        _ => "mixed",
+    }
+}
+
+/// Selects the entries that are listed or unzipped
+#[derive(Default)]
+struct EntryFilter {
+    regex: Option<fancy_regex::Regex>,
+    /// uncompressed size bounds, unlike `--max-size` they apply to each entry
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// with `--manifest-in`, the only names selected
+    names: Option<HashSet<String>>,
+    /// with `--ext`, the only extensions selected, lowercase and without the dot
+    extensions: Option<HashSet<String>>,
+    /// with `--within`, the directory entries are selected from, stripped when unzipping
+    within: Option<PathBuf>,
+    /// with `--filter`, a closure called with each entry, selecting those it returns true for
+    closure: Option<(nu_plugin::EngineInterface, Spanned<Closure>)>,
+    /// with `--since-archive`, the CRC-32 of the reference archive entries, by name
+    since: Option<HashMap<String, u32>>,
+}
+
+impl EntryFilter {
+    /// How `file` changed since the `--since-archive` reference: added, changed or same
+    fn change(&self, name: &str, file: &ZipFile) -> Option<&'static str> {
+        let since = self.since.as_ref()?;
+        Some(match since.get(name) {
+            None => "added",
+            Some(&crc) if crc != file.crc32() => "changed",
+            Some(_) => "same",
+        })
+    }
+
+    /// Whether `file`, with the given normalized name, is selected
+    fn matches(&self, name: &str, file: &ZipFile, log: &mut Log) -> bool {
+        // some archivers store bogus sizes on directory entries
+        let size = if file.is_dir() { 0 } else { file.size() };
+        if self
+            .names
+            .as_ref()
+            .is_some_and(|names| !names.contains(name))
+        {
+            return false;
+        }
+        if self.change(name, file) == Some("same") {
+            return false;
+        }
+        if self
+            .within
+            .as_ref()
+            .is_some_and(|within| !Path::new(name).starts_with(within))
+        {
+            return false;
+        }
+        if let Some(extensions) = &self.extensions {
+            // directories have no extension, nor have names like .bashrc
+            let extension = Path::new(name)
+                .extension()
+                .filter(|_| !name.ends_with('/'))
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            if !extension.is_some_and(|extension| extensions.contains(&extension)) {
+                return false;
+            }
+        }
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            log.debug(format!("Skipping {name}: size {size} is out of range"));
+            return false;
+        }
+        if let Some(regex) = &self.regex {
+            match regex.is_match(name) {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(e) => {
+                    log.warn(format!("Skipping {name}: {e}"));
+                    return false;
+                }
+            }
+        }
+        // last, as calling back into Nushell is by far the slowest check
+        if let Some((engine, closure)) = &self.closure {
+            let entry = filter_entry(name, file, closure.span);
+            let result = engine.eval_closure(closure, vec![entry.clone()], Some(entry));
+            return filter_selects(name, result, log);
+        }
+        true
+    }
+}
+
+/// The path the `--path-transform` closure, returning `result` for entry `name`, gives it
+fn transformed_path(
+    name: &str,
+    result: Result<Value, ShellError>,
+    span: Span,
+    log: &mut Log,
+) -> Result<PathBuf, SkipReason> {
+    let path = match result {
+        Ok(Value::String { val, .. }) => val,
+        Ok(Value::Nothing { .. }) => {
+            log.debug(format!("Skipping {name}: --path-transform returned null"));
+            return Err(SkipReason::Filtered);
+        }
+        Ok(value) => {
+            log.warn(format!(
+                "Skipping {name}: --path-transform returned {} instead of a string",
+                value.get_type()
+            ));
+            return Err(SkipReason::Other);
+        }
+        Err(e) => {
+            log.warn(format!("Skipping {name}: {e}"));
+            return Err(SkipReason::Other);
+        }
+    };
+    // like --add-prefix, only plain components, so that it stays in the target directory
+    match check_prefix(&path, span) {
+        Ok(path) if !path.as_os_str().is_empty() => Ok(path),
+        _ => {
+            log.warn(format!(
+                "Skipping {name}: --path-transform returned {path}, which isn't a relative path \
+                 inside the target directory"
+            ));
+            Err(SkipReason::Unsafe)
+        }
+    }
+}
+
+/// The record `--filter` closures are called with, as argument and input
+fn filter_entry(name: &str, file: &ZipFile, span: Span) -> Value {
+    // some archivers store bogus sizes on directory entries
+    let size = if file.is_dir() { 0 } else { file.size() };
+    let mut entry = Record::default();
+    entry.push("name", Value::string(name, span));
+    entry.push("size", Value::filesize(size as i64, span));
+    entry.push("modified", Value::date(entry_modified(file).into(), span));
+    entry.push("is_dir", Value::bool(file.is_dir(), span));
+    Value::record(entry, span)
+}
+
+/// Whether the `--filter` closure, returning `result` for entry `name`, selects it
+fn filter_selects(name: &str, result: Result<Value, ShellError>, log: &mut Log) -> bool {
+    match result {
+        Ok(Value::Bool { val, .. }) => val,
+        Ok(value) => {
+            log.warn(format!(
+                "Skipping {name}: --filter returned {} instead of a bool",
+                value.get_type()
+            ));
+            false
+        }
+        Err(e) => {
+            log.warn(format!("Skipping {name}: {e}"));
+            false
+        }
+    }
+}
+
+/// Parse `--ext` extensions, given as a list or comma separated, with or without a dot
+fn parse_extensions(value: Value) -> Result<HashSet<String>, LabeledError> {
+    let extensions = match value {
+        Value::List { .. } => Vec::<String>::from_value(value)?,
+        _ => vec![String::from_value(value)?],
+    };
+    Ok(extensions
+        .iter()
+        .flat_map(|extensions| extensions.split(','))
+        .map(|extension| extension.trim().trim_start_matches('.').to_lowercase())
+        .filter(|extension| !extension.is_empty())
+        .collect())
+}
+
+fn parse_regex(pattern: &str, span: Span) -> Result<fancy_regex::Regex, LabeledError> {
+    fancy_regex::Regex::new(pattern).map_err(|e| {
+        LabeledError::new(format!("Invalid regex {pattern}")).with_label(e.to_string(), span)
+    })
+}
+
+/// Options controlling what `--list` reports
+struct ListOptions {
+    filter: EntryFilter,
+    kind: EntryKind,
+    /// add a `modified_epoch` column with the modification time in Unix seconds
+    epoch: bool,
+    /// add a `crc32` column with the CRC-32 as 8 hex digits
+    crc_hex: bool,
+    /// add columns decoded from the central directory header
+    verbose: bool,
+    /// separator of the reported names, as stored if not given
+    path_style: Option<PathStyle>,
+    /// timestamp reported as modified, NTFS, then extended, then DOS time if not given
+    time_source: Option<TimeSource>,
+    /// add a `preview` column with this many leading bytes of each file
+    preview: Option<usize>,
+}
+
+impl ListOptions {
+    /// The columns listed for every entry, or `None` if the entry isn't selected
+    fn row(&self, file: &ZipFile, span: Span, log: &mut Log) -> Option<Record> {
+        let file_name = normalized_name(file.name())?;
+        if !self.kind.matches(file.is_dir()) || !self.filter.matches(file_name, file, log) {
+            return None;
+        }
+        let uncompressed_size = if file.is_dir() { 0 } else { file.size() };
+
+        let last_modified = match self.time_source {
+            Some(source) => source.modified(file),
+            None => entry_modified(file),
+        };
+
+        let mut row = Record::default();
+        let display_name = match self.path_style {
+            Some(style) => style.display(file_name),
+            None => file_name.to_string(),
+        };
+        row.push("name", Value::string(display_name, span));
+        row.push("size", Value::filesize(uncompressed_size as i64, span));
+        row.push("modified", Value::date(last_modified.into(), span));
+        row.push("depth", Value::int(entry_depth(file_name), span));
+        if let Some(change) = self.filter.change(file_name, file) {
+            row.push("change", Value::string(change, span));
+        }
+        if self.epoch {
+            row.push(
+                "modified_epoch",
+                Value::int(last_modified.timestamp(), span),
+            );
+        }
+        if self.crc_hex {
+            row.push(
+                "crc32",
+                Value::string(format!("{:08x}", file.crc32()), span),
+            );
+        }
+        Some(row)
+    }
+}
+
+/// Entry name with any leading `./` removed, or `None` for entries that only name the root of
+/// the archive, such as `.`, `./` or `/`
+fn normalized_name(name: &str) -> Option<&str> {
+    let mut name = name;
+    while let Some(rest) = name.strip_prefix("./") {
+        name = rest;
+    }
+    match name {
+        "" | "." | "/" => None,
+        name => Some(name),
+    }
+}
+
+/// Nesting level of an entry, 0 for top-level entries
+fn entry_depth(name: &str) -> i64 {
+    name.trim_end_matches('/').matches('/').count() as i64
+}
+
+/// Why an entry wasn't unzipped
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SkipReason {
+    /// encrypted, without a password that decrypts it
+    Encrypted,
+    /// compressed with a method this build can't decompress
+    Unsupported,
+    /// absolute, outside of the target directory or over the archive itself
+    Unsafe,
+    /// left out by the filters, nothing left of its path after stripping, or superseded by a
+    /// later entry with `--latest-only`
+    Filtered,
+    /// refused by `--confine`, its path resolving outside of the target directory
+    Confined,
+    /// data not matching its CRC32, with `--skip-errors`
+    Corrupt,
+    /// unreadable
+    Other,
+}
+
+/// Entries unzipped and skipped by reason, for `--report`
+#[derive(Default)]
+struct SkipReport {
+    extracted: usize,
+    skipped: [usize; 7],
+}
+
+impl SkipReport {
+    const KEYS: [(SkipReason, &'static str); 7] = [
+        (SkipReason::Encrypted, "skipped_encrypted"),
+        (SkipReason::Unsupported, "skipped_unsupported"),
+        (SkipReason::Unsafe, "skipped_unsafe"),
+        (SkipReason::Filtered, "filtered"),
+        (SkipReason::Confined, "refused"),
+        (SkipReason::Corrupt, "corrupted"),
+        (SkipReason::Other, "skipped_other"),
+    ];
+
+    fn add(&mut self, reason: SkipReason) {
+        if let Some(i) = Self::KEYS.iter().position(|(key, _)| *key == reason) {
+            self.skipped[i] += 1;
+        }
+    }
+
+    /// Add the counts of `other`, as for the archives nested in another
+    fn merge(&mut self, other: SkipReport) {
+        self.extracted += other.extracted;
+        for (count, other) in self.skipped.iter_mut().zip(other.skipped) {
+            *count += other;
+        }
+    }
+
+    fn count(&self, reason: SkipReason) -> usize {
+        Self::KEYS
+            .iter()
+            .position(|(key, _)| *key == reason)
+            .map_or(0, |i| self.skipped[i])
+    }
+
+    /// Whether every entry was unzipped, apart from those filtered out on purpose
+    fn complete(&self) -> bool {
+        Self::KEYS
+            .iter()
+            .zip(self.skipped)
+            .all(|((reason, _), count)| *reason == SkipReason::Filtered || count == 0)
+    }
+
+    fn into_value(self, span: Span) -> Value {
+        let mut record = Record::default();
+        record.push("extracted", Value::int(self.extracted as i64, span));
+        for ((_, key), count) in Self::KEYS.iter().zip(self.skipped) {
+            record.push(*key, Value::int(count as i64, span));
+        }
+        Value::record(record, span)
+    }
+}
+
+/// Options controlling how entries are extracted
+#[derive(Default)]
+struct ExtractOptions {
+    filter: EntryFilter,
+    force: bool,
+    /// number of leading path components to drop from every entry
+    strip_components: usize,
+    /// number of trailing directory components to drop from every entry, a file keeps its name
+    strip_trailing: usize,
+    /// relative path prepended to every entry after stripping
+    add_prefix: Option<PathBuf>,
+    /// maximum number of bytes written in total
+    max_size: Option<u64>,
+    /// report entries that would overwrite existing files before extracting
+    report_conflicts: bool,
+    /// refuse archives with suspicious entries instead of skipping them
+    strict: bool,
+    /// refuse entries whose path resolves outside of the target directory through symlinks
+    confine: bool,
+    /// with `--path-transform`, a closure called with each entry name, returning its path
+    transform: Option<(nu_plugin::EngineInterface, Spanned<Closure>)>,
+    /// flush each unzipped file to disk before going on with the next
+    fsync: bool,
+    /// drop the byte order mark text entries start with
+    strip_bom: bool,
+    /// where the archive starts in each file, see `--offset`
+    offset: Option<u64>,
+    /// with `--data`, the archive itself instead of a file
+    data: Option<Arc<[u8]>>,
+    /// skip entries with the hidden or system DOS attribute
+    skip_hidden: bool,
+    /// create reparse points as links on Windows instead of plain files
+    allow_reparse: bool,
+    /// fail for archives nothing is unzipped from
+    fail_if_empty: bool,
+    /// write entries with the same CRC32 and size only once, linking the others to it
+    dedupe: bool,
+    /// point in time after which unzipping is aborted
+    deadline: Option<Instant>,
+    /// candidate passwords for encrypted entries, tried in order
+    passwords: Vec<String>,
+    /// compute output paths from a template instead of the entry path
+    out_template: Option<OutTemplate>,
+    /// with `--rename-on-conflict`, how files that would overwrite existing ones are named
+    rename: Option<ConflictFormat>,
+    /// with `--out-encoding`, the encoding the names of unzipped files are written in
+    out_encoding: Option<OutEncoding>,
+    /// write files to a temporary name next to them and rename them once complete
+    atomic: bool,
+    /// refuse archives with more entries
+    max_entries: Option<usize>,
+    /// unix mode for extracted files, regardless of the archive
+    file_mode: Option<u32>,
+    /// unix mode for extracted directories, regardless of the archive
+    dir_mode: Option<u32>,
+    /// with `--recursive-zips`, how many levels of nested zips are unzipped
+    nested_depth: Option<usize>,
+    /// with `--recursive-zips`, where the entries named like zips were unzipped to
+    nested_zips: RefCell<Vec<PathBuf>>,
+    /// unzip into a directory named after the archive unless its entries share one
+    keep_root: bool,
+    /// skip the entries an interrupted run completed
+    resume: bool,
+    /// report entries replaced by a later one with the same name
+    latest_only: bool,
+    /// warn about `--manifest-in` names an archive lacks and skip corrupted entries instead of
+    /// failing
+    skip_errors: bool,
+    /// with `--preserve-ownership`, the `--owner-map` rules applied to the stored owners
+    ownership: Option<Vec<OwnerRule>>,
+    /// with `--events`, where to send a record for each step of unzipping
+    events: Option<Sender<Value>>,
+    /// counts of unzipped and skipped entries over all archives, returned with `--report`
+    report: RefCell<SkipReport>,
+    /// with `--report-by-size`, the path and size of every unzipped file
+    sizes: Option<RefCell<Vec<(PathBuf, u64)>>>,
+}
+
+impl ExtractOptions {
+    /// Count an entry skipped for `reason` and report it with `--events`
+    fn skip(&self, reason: SkipReason, name: &str, span: Span) {
+        self.report.borrow_mut().add(reason);
+        self.emit("skip", name, 0, span);
+    }
+
+    /// Options for the archives nested in the one these options are for, with
+    /// `--recursive-zips`
+    ///
+    /// Filters and path options are meant for the names of the outer archive, so they are left
+    /// out. The counts and sizes start over, see [`Self::merge_nested`].
+    fn nested(&self) -> ExtractOptions {
+        ExtractOptions {
+            force: self.force,
+            max_size: self.max_size,
+            strict: self.strict,
+            confine: self.confine,
+            fsync: self.fsync,
+            strip_bom: self.strip_bom,
+            skip_hidden: self.skip_hidden,
+            allow_reparse: self.allow_reparse,
+            dedupe: self.dedupe,
+            deadline: self.deadline,
+            passwords: self.passwords.clone(),
+            rename: self.rename.clone(),
+            out_encoding: self.out_encoding.clone(),
+            atomic: self.atomic,
+            max_entries: self.max_entries,
+            file_mode: self.file_mode,
+            dir_mode: self.dir_mode,
+            nested_depth: self.nested_depth,
+            skip_errors: self.skip_errors,
+            ownership: self.ownership.clone(),
+            events: self.events.clone(),
+            sizes: self.sizes.as_ref().map(|_| RefCell::default()),
+            ..Default::default()
+        }
+    }
+
+    /// Add the counts and sizes of the options from [`Self::nested`] to these
+    fn merge_nested(&self, nested: ExtractOptions) {
+        self.report.borrow_mut().merge(nested.report.into_inner());
+        if let (Some(sizes), Some(nested)) = (&self.sizes, nested.sizes) {
+            sizes.borrow_mut().extend(nested.into_inner());
+        }
+    }
+
+    /// Remember a file unzipped from an entry named like a zip, to unzip it in turn with
+    /// `--recursive-zips`
+    fn unzipped(&self, file: &ZipFile, out_path: &Path) {
+        if self.nested_depth.is_some() && file.name().to_ascii_lowercase().ends_with(".zip") {
+            self.nested_zips.borrow_mut().push(out_path.to_path_buf());
+        }
+    }
+
+    /// Report a step of unzipping with `--events`
+    fn emit(&self, event: &str, name: &str, bytes: u64, span: Span) {
+        if let Some(events) = &self.events {
+            let mut record = Record::default();
+            record.push("event", Value::string(event, span));
+            record.push("name", Value::string(name, span));
+            record.push("bytes", Value::filesize(bytes as i64, span));
+            // the stream may have been dropped, unzipping goes on regardless
+            let _ = events.send(Value::record(record, span));
+        }
+    }
+
+    /// Owner to give the extracted `file`, if ownership is preserved and the entry has one
+    fn owner(&self, file: &ZipFile) -> Option<(u32, u32)> {
+        let rules = self.ownership.as_ref()?;
+        let owner = entry_owner(file)?;
+        // unmapped owners are kept as stored
+        Some(
+            rules
+                .iter()
+                .find(|rule| rule.from == owner)
+                .map_or(owner, |rule| rule.to),
+        )
+    }
+
+    fn check_deadline(&self, state: &ExtractState, span: Span) -> UnzipResult<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(UnzipError::TimedOut {
+                extracted: state.extracted,
+                span,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Bytes per second as MB/s, to tell decompression bound from disk bound runs with `--debug`
+fn throughput(bytes: u64, elapsed: Duration) -> String {
+    if elapsed.is_zero() {
+        return "too fast to measure".to_string();
+    }
+    format!("{:.1} MB/s", bytes as f64 / elapsed.as_secs_f64() / 1e6)
+}
+
+/// What `unzip_file` needs to know about an archive besides its entries
+#[derive(Default)]
+struct ArchiveRun {
+    /// where completed entries are recorded with `--resume`
+    resume_file: Option<PathBuf>,
+    /// indexes of the entries skipped with `--skip-hidden`
+    hidden: HashSet<usize>,
+    /// indexes of the reparse points created as links with `--allow-reparse`
+    reparse: HashSet<usize>,
+    /// the archive file with symlinks resolved, which no entry may overwrite
+    archive_path: Option<PathBuf>,
+}
+
+impl ArchiveRun {
+    /// Read the attributes the options need from the central directory in `raw`
+    fn new<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        raw: &mut (impl Read + Seek),
+        options: &ExtractOptions,
+        archive_path: Option<&Path>,
+        resume_file: Option<PathBuf>,
+    ) -> Self {
+        let mut run = ArchiveRun {
+            resume_file,
+            archive_path: archive_path.and_then(|path| path.canonicalize().ok()),
+            ..Default::default()
+        };
+        if options.skip_hidden {
+            run.hidden = flagged_entries(archive, raw, CentralHeader::is_hidden);
+        }
+        if options.allow_reparse {
+            run.reparse = flagged_entries(archive, raw, CentralHeader::is_reparse_point);
+        }
+        run
+    }
+
+    /// Whether unzipping to `out_path` would overwrite the archive being read
+    fn overwrites_archive(&self, out_path: &Path) -> bool {
+        self.archive_path
+            .as_ref()
+            .is_some_and(|archive| resolve_path(out_path).is_ok_and(|path| path == *archive))
+    }
+}
+
+/// Bookkeeping across the entries of one archive while unzipping
+#[derive(Default)]
+struct ExtractState {
+    total_written: u64,
+    /// number of entries extracted so far
+    extracted: usize,
+    /// time spent extracting entries, reported with `--debug`
+    elapsed: Duration,
+    /// first path written for each (CRC32, size), for `--dedupe-by-crc`
+    written: HashMap<(u32, u64), PathBuf>,
+}
+
+/// Where `write_entry` writes the data of an entry, so that outputs other than files on disk
+/// can share it
+trait EntryDestination {
+    /// A writer for the entry that would be extracted to `path`, flushed once it's written
+    fn create(&mut self, path: &Path) -> std::io::Result<Box<dyn Write + '_>>;
+}
+
+/// Files on disk, created at the path of each entry
+struct FileDestination {
+    /// sync each file to disk when it's flushed, see `--fsync`
+    fsync: bool,
+}
+
+impl EntryDestination for FileDestination {
+    fn create(&mut self, path: &Path) -> std::io::Result<Box<dyn Write + '_>> {
+        let file = std::fs::File::create(path)?;
+        Ok(if self.fsync {
+            Box::new(std::io::BufWriter::new(SyncedFile(file)))
+        } else {
+            Box::new(std::io::BufWriter::new(file))
+        })
+    }
+}
+
+/// A file that flushing syncs to disk
+struct SyncedFile(std::fs::File);
+
+impl Write for SyncedFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.sync_all()
+    }
+}
+
+/// Make `dest` a hard link to `original`, or a copy where hard links aren't used
+fn link_or_copy(original: &Path, dest: &Path) -> std::io::Result<()> {
+    if dest.exists() {
+        std::fs::remove_file(dest)?;
+    }
+    #[cfg(unix)]
+    {
+        std::fs::hard_link(original, dest)
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::copy(original, dest).map(|_| ())
+    }
+}
+
+impl ExtractOptions {
+    /// Map an entry's enclosed name to the path it is extracted to, relative to the target
+    /// directory, or `None` if nothing is left of it after stripping
+    fn relative_path(&self, enclosed_name: &Path, is_dir: bool) -> Option<PathBuf> {
+        // entries selected by --within are below its directory, which is dropped
+        let within = self
+            .filter
+            .within
+            .as_ref()
+            .map_or(0, |within| within.components().count());
+        let mut components: Vec<_> = enclosed_name
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .skip(within + self.strip_components)
+            .collect();
+        // a file goes up into its grandparent for --strip-trailing 1, a directory merges with
+        // its parent
+        let dirs = components.len() - usize::from(!is_dir && !components.is_empty());
+        components.drain(dirs.saturating_sub(self.strip_trailing)..dirs);
+        let path: PathBuf = components.into_iter().collect();
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+
+        match &self.add_prefix {
+            Some(prefix) => Some(prefix.join(path)),
+            None => Some(path),
+        }
+    }
+
+    /// Path an entry is extracted to, or why the entry is skipped
+    fn out_path(&self, file: &ZipFile, dir: &Path, log: &mut Log) -> Result<PathBuf, SkipReason> {
+        let Some(name) = normalized_name(file.name()) else {
+            log.debug(format!("Skipping {}", file.name()));
+            return Err(SkipReason::Filtered);
+        };
+        if !self.filter.matches(name, file, log) {
+            return Err(SkipReason::Filtered);
+        }
+        let enclosed_name = match enclosed_path(file.name()) {
+            Some(path) => path,
+            None if is_absolute_name(file.name()) => {
+                log.warn(format!("Skipping {}: path is absolute", file.name()));
+                return Err(SkipReason::Unsafe);
+            }
+            None => {
+                log.warn(format!(
+                    "Skipping {}: path is outside of the target directory",
+                    file.name()
+                ));
+                return Err(SkipReason::Unsafe);
+            }
+        };
+        let path = match &self.transform {
+            Some(transform) => self.transformed(transform, name, log)?,
+            None => self
+                .relative_path(&enclosed_name, file.is_dir())
+                .ok_or(SkipReason::Filtered)?,
+        };
+        let Some(template) = &self.out_template else {
+            let out_path = self.encoded(file, dir, dir.join(path), log)?;
+            return self.confined(file, dir, out_path, log);
+        };
+
+        let out_path = dir.join(template.expand(dir, &path, entry_modified(file)));
+        let inside = out_path.strip_prefix(dir).is_ok_and(|relative| {
+            !relative.as_os_str().is_empty()
+                && relative
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+        });
+        if !inside {
+            log.warn(format!(
+                "Skipping {}: {} is outside of the target directory",
+                file.name(),
+                out_path.display()
+            ));
+            return Err(SkipReason::Unsafe);
+        }
+        let out_path = self.encoded(file, dir, out_path, log)?;
+        self.confined(file, dir, out_path, log)
+    }
+
+    /// `out_path` with the names below `dir` in the `--out-encoding`, if any
+    fn encoded(
+        &self,
+        file: &ZipFile,
+        dir: &Path,
+        out_path: PathBuf,
+        log: &mut Log,
+    ) -> Result<PathBuf, SkipReason> {
+        let Some(encoding) = &self.out_encoding else {
+            return Ok(out_path);
+        };
+        encoding.encoded_path(dir, &out_path).ok_or_else(|| {
+            log.warn(format!(
+                "Skipping {}: its name can't be written in {}",
+                file.name(),
+                encoding.encoding.name()
+            ));
+            SkipReason::Unsupported
+        })
+    }
+
+    /// Path the `--path-transform` closure gives the entry `name`, relative to the target
+    /// directory, skipping the entry for null
+    fn transformed(
+        &self,
+        (engine, closure): &(nu_plugin::EngineInterface, Spanned<Closure>),
+        name: &str,
+        log: &mut Log,
+    ) -> Result<PathBuf, SkipReason> {
+        let entry = Value::string(name, closure.span);
+        let result = engine.eval_closure(closure, vec![entry.clone()], Some(entry));
+        transformed_path(name, result, closure.span, log)
+    }
+
+    /// With `--confine`, check that `out_path` is still inside `dir` once symlinks already on
+    /// disk are resolved
+    fn confined(
+        &self,
+        file: &ZipFile,
+        dir: &Path,
+        out_path: PathBuf,
+        log: &mut Log,
+    ) -> Result<PathBuf, SkipReason> {
+        if !self.confine {
+            return Ok(out_path);
+        }
+        match (resolve_path(&out_path), resolve_path(dir)) {
+            (Ok(resolved), Ok(dir)) if resolved.starts_with(&dir) => Ok(out_path),
+            (Ok(resolved), Ok(_)) => {
+                log.warn(format!(
+                    "Refusing {}: {} resolves to {}, outside of the target directory",
+                    file.name(),
+                    out_path.display(),
+                    resolved.display()
+                ));
+                Err(SkipReason::Confined)
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log.warn(format!("Refusing {}: {e}", file.name()));
+                Err(SkipReason::Confined)
+            }
+        }
+    }
+}
+
+/// Placeholders understood by `--out-template`
+#[derive(Clone, Copy)]
+enum Placeholder {
+    /// the target directory
+    Dir,
+    /// the path the entry would be extracted to, relative to the target directory
+    Path,
+    /// the base name of the entry
+    Name,
+    Year,
+    Month,
+    Day,
+}
+
+impl Placeholder {
+    const ALL: [(&'static str, Placeholder); 6] = [
+        ("dir", Placeholder::Dir),
+        ("path", Placeholder::Path),
+        ("name", Placeholder::Name),
+        ("year", Placeholder::Year),
+        ("month", Placeholder::Month),
+        ("day", Placeholder::Day),
+    ];
+}
+
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// A parsed `--out-template` such as `{dir}/{year}/{name}`
+struct OutTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl OutTemplate {
+    fn parse(template: &str, span: Span) -> Result<Self, LabeledError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err(LabeledError::new(format!("Invalid template {template}"))
+                    .with_label("Unclosed {", span));
+            };
+            let key = &rest[start + 1..start + len];
+            let Some((_, placeholder)) = Placeholder::ALL.iter().find(|(name, _)| *name == key)
+            else {
+                let known: Vec<_> = Placeholder::ALL
+                    .iter()
+                    .map(|(name, _)| format!("{{{name}}}"))
+                    .collect();
+                return Err(LabeledError::new(format!(
+                    "Unknown placeholder {{{key}}} in template"
+                ))
+                .with_label(format!("Expected one of {}", known.join(", ")), span));
+            };
+            parts.push(TemplatePart::Placeholder(*placeholder));
+            rest = &rest[start + len + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    fn expand(&self, dir: &Path, path: &Path, modified: chrono::DateTime<chrono::Local>) -> String {
+        use chrono::Datelike;
+
+        let mut expanded = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => expanded.push_str(literal),
+                TemplatePart::Placeholder(placeholder) => match placeholder {
+                    Placeholder::Dir => expanded.push_str(&dir.to_string_lossy()),
+                    Placeholder::Path => expanded.push_str(&path.to_string_lossy()),
+                    Placeholder::Name => expanded.push_str(
+                        &path
+                            .file_name()
+                            .map(|name| name.to_string_lossy())
+                            .unwrap_or_default(),
+                    ),
+                    Placeholder::Year => expanded.push_str(&format!("{:04}", modified.year())),
+                    Placeholder::Month => expanded.push_str(&format!("{:02}", modified.month())),
+                    Placeholder::Day => expanded.push_str(&format!("{:02}", modified.day())),
+                },
+            }
+        }
+        expanded
+    }
+}
+
+/// What `--out-encoding` does with names having characters the encoding lacks
+#[derive(Clone, Copy, PartialEq)]
+enum Unmappable {
+    /// skip the entry
+    Skip,
+    /// write `_` in place of the character
+    Replace,
+    /// fail before unzipping anything
+    Error,
+}
+
+/// Encoding names of unzipped files are written in with `--out-encoding`
+///
+/// Only unix file names are bytes, Windows stores them as UTF-16 whatever the encoding, so
+/// there only the characters the encoding lacks are handled.
+#[derive(Clone)]
+struct OutEncoding {
+    encoding: &'static encoding_rs::Encoding,
+    unmappable: Unmappable,
+}
+
+impl OutEncoding {
+    fn parse(
+        label: &Spanned<String>,
+        unmappable: Option<Spanned<String>>,
+    ) -> Result<Self, LabeledError> {
+        let encoding =
+            encoding_rs::Encoding::for_label(label.item.as_bytes()).ok_or_else(|| {
+                LabeledError::new(format!("Unknown encoding {}", label.item)).with_label(
+                    "Expected a label like shift_jis, gbk or windows-1252",
+                    label.span,
+                )
+            })?;
+        let unmappable = match unmappable {
+            None => Unmappable::Error,
+            Some(policy) => match policy.item.as_str() {
+                "skip" => Unmappable::Skip,
+                "replace" => Unmappable::Replace,
+                "error" => Unmappable::Error,
+                _ => {
+                    return Err(LabeledError::new(format!("Unknown policy {}", policy.item))
+                        .with_label("Expected skip, replace or error", policy.span))
+                }
+            },
+        };
+        Ok(Self {
+            encoding,
+            unmappable,
+        })
+    }
+
+    /// `name` in the encoding, or `None` if it has characters the encoding lacks and these
+    /// aren't replaced
+    fn encode(&self, name: &str) -> Option<Vec<u8>> {
+        let mut encoder = self.encoding.new_encoder();
+        let mut encoded = Vec::new();
+        let mut rest = name;
+        loop {
+            encoded.reserve(rest.len() * 4 + 16);
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut encoded, true);
+            rest = &rest[read..];
+            match result {
+                encoding_rs::EncoderResult::InputEmpty => return Some(encoded),
+                encoding_rs::EncoderResult::OutputFull => {}
+                encoding_rs::EncoderResult::Unmappable(_)
+                    if self.unmappable == Unmappable::Replace =>
+                {
+                    encoded.push(b'_');
+                }
+                encoding_rs::EncoderResult::Unmappable(_) => return None,
+            }
+        }
+    }
+
+    /// `path` below `dir` with the names of its components encoded
+    fn encoded_path(&self, dir: &Path, path: &Path) -> Option<PathBuf> {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            return Some(path.to_path_buf());
+        };
+        let mut encoded = dir.to_path_buf();
+        for component in relative.components() {
+            let name = component.as_os_str().to_string_lossy();
+            let bytes = self.encode(&name)?;
+            #[cfg(unix)]
+            let name: std::ffi::OsString = std::os::unix::ffi::OsStringExt::from_vec(bytes);
+            #[cfg(not(unix))]
+            let name = self
+                .encoding
+                .decode_without_bom_handling(&bytes)
+                .0
+                .into_owned();
+            encoded.push(name);
+        }
+        Some(encoded)
+    }
+}
+
+/// Parts of a `--conflict-format` such as `{stem} ({n}){ext}`
+#[derive(Clone)]
+enum ConflictPart {
+    Literal(String),
+    /// the file name without its extension
+    Stem,
+    /// the number making the name unique, from 1
+    Number,
+    /// the extension with its leading dot, empty if there is none
+    Ext,
+}
+
+/// Names given with `--rename-on-conflict` to files that would overwrite existing ones
+#[derive(Clone)]
+struct ConflictFormat {
+    parts: Vec<ConflictPart>,
+}
+
+impl ConflictFormat {
+    const DEFAULT: &'static str = "{stem} ({n}){ext}";
+
+    fn parse(format: &str, span: Span) -> Result<Self, LabeledError> {
+        let invalid = |reason: &str| {
+            LabeledError::new(format!("Invalid conflict format {format}")).with_label(reason, span)
+        };
+        if format.contains(['/', '\\']) {
+            return Err(invalid(
+                "Renamed files stay in their directory, no separator allowed",
+            ));
+        }
+        let mut parts = Vec::new();
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(ConflictPart::Literal(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err(invalid("Unclosed {"));
+            };
+            parts.push(match &rest[start + 1..start + len] {
+                "stem" => ConflictPart::Stem,
+                "n" => ConflictPart::Number,
+                "ext" => ConflictPart::Ext,
+                key => {
+                    return Err(LabeledError::new(format!(
+                        "Unknown placeholder {{{key}}} in conflict format"
+                    ))
+                    .with_label("Expected one of {stem}, {n}, {ext}", span))
+                }
+            });
+            rest = &rest[start + len + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(ConflictPart::Literal(rest.to_string()));
+        }
+        // without a number, every renamed file would get the same name
+        if !parts
+            .iter()
+            .any(|part| matches!(part, ConflictPart::Number))
+        {
+            return Err(invalid("{n} is needed to tell renamed files apart"));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The first numbered name for `path` that no file has yet
+    fn free_path(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        (1..)
+            .map(|n: u64| {
+                let mut name = String::new();
+                for part in &self.parts {
+                    match part {
+                        ConflictPart::Literal(literal) => name.push_str(literal),
+                        ConflictPart::Stem => name.push_str(&stem),
+                        ConflictPart::Number => name.push_str(&n.to_string()),
+                        ConflictPart::Ext => name.push_str(&ext),
+                    }
+                }
+                path.with_file_name(name)
+            })
+            .find(|path| path.symlink_metadata().is_err())
+            .expect("some number is free")
+    }
+}
+
+/// Whether all entries are below one top-level directory
+fn has_single_root<'a>(names: impl Iterator<Item = &'a str>) -> bool {
+    let mut root = None;
+    for name in names.filter_map(normalized_name) {
+        let Some((first, _)) = name.split_once('/') else {
+            return false;
+        };
+        match root {
+            Some(root) if root != first => return false,
+            _ => root = Some(first),
+        }
+    }
+    root.is_some()
+}
+
+/// Whether an entry's name is absolute, escapes the target directory or walks up at all
+///
+/// Built on [`enclosed_path`], so names unzipping skips as unsafe are always listed as such.
+fn is_unsafe_name(name: &str) -> bool {
+    enclosed_path(name).is_none_or(|path| path.components().any(|c| c == Component::ParentDir))
+}
+
+/// Relative path of an entry, or `None` if it's absolute or escapes the target directory
+///
+/// Unlike [`ZipFile::enclosed_name`], both `/` and `\\` separate components on every platform, so
+/// archives made on Windows unzip to the same tree everywhere.
+fn enclosed_path(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') || is_absolute_name(name) {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    let mut depth = 0usize;
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                depth = depth.checked_sub(1)?;
+                path.push(part);
+            }
+            part => {
+                // a drive letter within the name would replace the path on Windows
+                let mut components = Path::new(part).components();
+                if !matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(_)), None)
+                ) {
+                    return None;
+                }
+                depth += 1;
+                path.push(part);
+            }
+        }
+    }
+    Some(path)
+}
+
+/// Create the link a reparse point entry stands for, with `--allow-reparse`
+///
+/// The entry data is the link target, like for unix symlinks, and has to be a relative path
+/// staying inside the target directory. Links are created with the symlink APIs, which may
+/// need Developer Mode or the privilege to create symbolic links.
+fn create_reparse_point(
+    file: &mut ZipFile,
+    out_path: &Path,
+    force: bool,
+    log: &mut Log,
+) -> Result<(), SkipReason> {
+    let name = file.name().to_string();
+    let mut target = String::new();
+    // a link target is a path, anything longer isn't one
+    if file.size() > 4096 || file.read_to_string(&mut target).is_err() || target.is_empty() {
+        log.warn(format!(
+            "Skipping {name}: its reparse point target isn't a path"
+        ));
+        return Err(SkipReason::Unsafe);
+    }
+    let parent = name.trim_end_matches('/').rsplit_once(['/', '\\']);
+    let resolved = match parent {
+        Some((parent, _)) => format!("{parent}/{target}"),
+        None => target.clone(),
+    };
+    if is_absolute_name(&target) || enclosed_path(&resolved).is_none() {
+        log.warn(format!(
+            "Skipping {name}: its reparse point target {target} is outside of the target \
+             directory"
+        ));
+        return Err(SkipReason::Unsafe);
+    }
+    if out_path.symlink_metadata().is_ok() {
+        if !force {
+            log.warn(format!("Skipping {name}: {} exists", out_path.display()));
+            return Err(SkipReason::Other);
+        }
+        let _ = std::fs::remove_file(out_path);
+    }
+    if let Some(out_dir) = out_path.parent() {
+        let _ = std::fs::create_dir_all(out_dir);
+    }
+    let target = target.replace('/', "\\");
+    #[cfg(windows)]
+    let created = if file.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, out_path)
+    } else {
+        std::os::windows::fs::symlink_file(&target, out_path)
+    };
+    #[cfg(not(windows))]
+    let created: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+    created.map_err(|e| {
+        log.warn(format!(
+            "Skipping {name}: fail to create the link to {target}: {e}"
+        ));
+        SkipReason::Other
+    })
+}
+
+/// Whether an entry's raw name is an absolute path, on any platform
+fn is_absolute_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    name.starts_with('/')
+        || name.starts_with('\\')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// `path` with symlinks resolved, like `canonicalize` but for paths that don't exist yet: the
+/// longest existing ancestor is resolved and the rest appended
+fn resolve_path(path: &Path) -> std::io::Result<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        match existing.canonicalize() {
+            Ok(resolved) => {
+                return Ok(missing
+                    .into_iter()
+                    .rev()
+                    .fold(resolved, |path, c| path.join(c)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e);
+                };
+                missing.push(name);
+                existing = parent;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Parse an octal mode like "0644" for `--chmod` and `--chmod-dirs`
+fn parse_mode(mode: &str, span: Span) -> Result<u32, LabeledError> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| {
+            LabeledError::new(format!("Invalid mode {mode}"))
+                .with_label("Expected an octal mode like 0644", span)
+        })
+}
+
+/// Set the mode of an extracted file or directory, a no-op on other platforms than unix
+fn set_mode(path: &Path, mode: Option<u32>, span: Span) -> UnzipResult<()> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(|error| {
+            UnzipError::SetMode {
+                path: path.to_path_buf(),
+                error,
+                span,
+            }
+        })?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode, span);
+    Ok(())
+}
+
+/// How many levels of nested zips `--recursive-zips` unzips without `--max-depth`
+const DEFAULT_NESTED_DEPTH: usize = 5;
+
+/// Name of the file next to the unzipped entries recording which of them are done
+fn resume_file_name(zip_file_path: &Path) -> String {
+    let name = zip_file_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    format!(".{name}.unzip-resume")
+}
+
+/// Indices of the entries a previous `--resume` run completed, one per line
+fn read_resume_file(path: &Path) -> std::io::Result<HashSet<usize>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Fail for archives with more entries than `--max-entries` allows
+fn check_entry_count(
+    count: usize,
+    max_entries: Option<usize>,
+    span: Span,
+) -> Result<(), LabeledError> {
+    match max_entries {
+        Some(max_entries) if count > max_entries => Err(LabeledError::new(format!(
+            "Archive has {count} entries, more than the limit of {max_entries}"
+        ))
+        .with_label("Use --max-entries to raise the limit", span)),
+        _ => Ok(()),
+    }
+}
+
+/// Fail for entries `--strict` refuses to unzip, and warn about implausible sizes otherwise
+///
+/// `data_end` is where the entry data ends, the start of the central directory.
+fn check_entry(
+    file: &ZipFile,
+    data_end: u64,
+    strict: bool,
+    span: Span,
+    log: &mut Log,
+) -> Result<(), LabeledError> {
+    if strict && is_absolute_name(file.name()) {
+        return Err(
+            LabeledError::new(format!("Archive contains absolute path {}", file.name()))
+                .with_label("Refusing to unzip with --strict", span),
+        );
+    }
+    if let Some(problem) = implausible_size(file, data_end) {
+        if strict {
+            return Err(LabeledError::new(format!(
+                "Archive entry {} has an implausible size",
+                file.name()
+            ))
+            .with_label(format!("{problem}, refusing to unzip with --strict"), span));
+        }
+        log.warn(format!("{}: {problem}", file.name()));
+    }
+    Ok(())
+}
+
+/// 100ns ticks between 1601-01-01, the NTFS epoch, and the unix epoch
+const NTFS_EPOCH_OFFSET: i64 = 116_444_736_000_000_000;
+
+fn ntfs_to_datetime(ticks: u64) -> Option<chrono::DateTime<chrono::Utc>> {
+    let ticks = i64::try_from(ticks).ok()?.checked_sub(NTFS_EPOCH_OFFSET)?;
+    chrono::DateTime::from_timestamp(
+        ticks.div_euclid(10_000_000),
+        (ticks.rem_euclid(10_000_000) * 100) as u32,
+    )
+}
+
+/// Modification time of an entry
+///
+/// Prefers the NTFS extra field (100ns resolution) over the extended timestamp
+/// (1s resolution), and falls back to the DOS time (2s resolution, local time).
+fn entry_modified(file: &ZipFile) -> chrono::DateTime<chrono::Local> {
+    match extra_timestamps(file) {
+        (Some(timestamp), _) | (None, Some(timestamp)) => timestamp.into(),
+        (None, None) => dos_modified(file),
+    }
+}
+
+/// Modification times of the NTFS and extended timestamp extra fields of an entry
+fn extra_timestamps(
+    file: &ZipFile,
+) -> (
+    Option<chrono::DateTime<chrono::Utc>>,
+    Option<chrono::DateTime<chrono::Utc>>,
+) {
+    let mut ntfs = None;
+    let mut extended = None;
+    for field in file.extra_data_fields() {
+        match field {
+            ExtraField::Ntfs(ntfs_) => ntfs = ntfs.or(ntfs_to_datetime(ntfs_.mtime())),
+            ExtraField::ExtendedTimestamp(timestamp) => {
+                extended = extended.or(timestamp
+                    .mod_time()
+                    .and_then(|t| chrono::DateTime::from_timestamp(t as i64, 0)))
+            }
+        }
+    }
+    (ntfs, extended)
+}
+
+/// Modification time of an entry from its DOS time only, see `--quick`
+fn dos_modified(file: &ZipFile) -> chrono::DateTime<chrono::Local> {
+    let zip_dt = file.last_modified().unwrap_or_default();
+    let naive_dt: chrono::NaiveDateTime = zip_dt.try_into().unwrap_or_default();
+    naive_dt
+        .and_local_timezone(chrono::Local)
+        .single()
+        .unwrap_or_default()
+}
+
+/// All the metadata of one entry, returned by `--entry-info`
+fn entry_info(file: &ZipFile, span: Span) -> Value {
+    let mut record = Record::default();
+    record.push("name", Value::string(file.name(), span));
+    record.push("size", Value::filesize(file.size() as i64, span));
+    record.push(
+        "compressed_size",
+        Value::filesize(file.compressed_size() as i64, span),
+    );
+    record.push("crc32", Value::int(file.crc32() as i64, span));
+    record.push("modified", Value::date(entry_modified(file).into(), span));
+    record.push(
+        "compression",
+        Value::string(method_name(file.compression()), span),
+    );
+    record.push("encrypted", Value::bool(file.encrypted(), span));
+    record.push(
+        "unix_mode",
+        file.unix_mode()
+            .map_or(Value::nothing(span), |mode| Value::int(mode as i64, span)),
+    );
+    record.push("comment", Value::string(file.comment(), span));
+    Value::record(record, span)
+}
+
+/// Number of single character insertions, deletions and substitutions from `a` to `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Up to three of `names` close to `name`, the closest first
+///
+/// A name is close if it starts with `name`, or is at most a third of its length of edits away.
+fn close_names<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut close: Vec<_> = names
+        .filter_map(|candidate| {
+            let distance = if candidate.starts_with(name) {
+                0
+            } else {
+                edit_distance(name, candidate)
+            };
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close.dedup();
+    close.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Encryption of an entry: "none", "zipcrypto" or "aes128"/"aes192"/"aes256"
+fn entry_encryption(file: &ZipFile) -> &'static str {
+    if !file.encrypted() {
+        return "none";
+    }
+    // WinZip AES entries carry an extra field with the key strength
+    let mut extra = file.extra_data().unwrap_or_default();
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len).unwrap_or_default();
+        if id == 0x9901 && data.len() >= 5 {
+            return match data[4] {
+                1 => "aes128",
+                2 => "aes192",
+                3 => "aes256",
+                _ => "aes",
+            };
+        }
+        extra = extra.get(4 + len..).unwrap_or_default();
+    }
+    "zipcrypto"
+}
+
+/// Extra field ids this plugin or the zip crate decode: zip64, NTFS, extended timestamp,
+/// Info-ZIP Unicode path and comment, Info-ZIP Unix and WinZip AES
+const KNOWN_EXTRA_FIELDS: [u16; 7] = [0x0001, 0x000a, 0x5455, 0x6375, 0x7075, 0x7875, 0x9901];
+
+/// Extra fields of an entry not in `KNOWN_EXTRA_FIELDS`, as `{tag, len}` records, to spot
+/// tool specific metadata
+fn custom_fields(file: &ZipFile, span: Span) -> Value {
+    let mut fields = vec![];
+    let mut extra = file.extra_data().unwrap_or_default();
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        if !KNOWN_EXTRA_FIELDS.contains(&id) {
+            let mut field = Record::new();
+            field.push("tag", Value::string(format!("{id:#06x}"), span));
+            field.push("len", Value::int(len as i64, span));
+            fields.push(Value::record(field, span));
+        }
+        extra = extra.get(4 + len..).unwrap_or_default();
+    }
+    Value::list(fields, span)
+}
+
+/// Owner stored in the Info-ZIP Unix extra field (0x7875) of an entry, as uid and gid
+fn entry_owner(file: &ZipFile) -> Option<(u32, u32)> {
+    let mut extra = file.extra_data().unwrap_or_default();
+    while extra.len() >= 4 {
+        let id = u16::from_le_bytes([extra[0], extra[1]]);
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + len).unwrap_or_default();
+        // version 1, then the size of the uid, the uid, the size of the gid and the gid
+        if id == 0x7875 && data.first() == Some(&1) {
+            let mut rest = &data[1..];
+            let mut id = || {
+                let (&size, tail) = rest.split_first()?;
+                let bytes = tail.get(..size as usize).filter(|bytes| bytes.len() <= 4)?;
+                rest = &tail[size as usize..];
+                let mut le = [0; 4];
+                le[..bytes.len()].copy_from_slice(bytes);
+                Some(u32::from_le_bytes(le))
+            };
+            return Some((id()?, id()?));
+        }
+        extra = extra.get(4 + len..).unwrap_or_default();
+    }
+    None
+}
+
+/// An `--owner-map` rule, translating a stored uid and gid
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct OwnerRule {
+    from: (u32, u32),
+    to: (u32, u32),
+}
+
+/// Parse `--owner-map` rules like "1000:1000=0:0", a single one or a list
+fn parse_owner_rules(value: Value, span: Span) -> Result<Vec<OwnerRule>, LabeledError> {
+    let rules = match value {
+        Value::List { .. } => Vec::<String>::from_value(value)?,
+        _ => vec![String::from_value(value)?],
+    };
+    let owner = |owner: &str| {
+        let (uid, gid) = owner.split_once(':')?;
+        Some((uid.parse().ok()?, gid.parse().ok()?))
+    };
+    rules
+        .iter()
+        .map(|rule| {
+            rule.split_once('=')
+                .and_then(|(from, to)| {
+                    Some(OwnerRule {
+                        from: owner(from)?,
+                        to: owner(to)?,
+                    })
+                })
+                .ok_or_else(|| {
+                    LabeledError::new(format!("Invalid owner mapping {rule}"))
+                        .with_label("Expected uid:gid=uid:gid, like 1000:1000=0:0", span)
+                })
+        })
+        .collect()
+}
+
+/// Set the owner of an extracted file or directory, a no-op on other platforms than unix
+fn set_owner(path: &Path, owner: Option<(u32, u32)>, span: Span) -> UnzipResult<()> {
+    #[cfg(unix)]
+    if let Some((uid, gid)) = owner {
+        std::os::unix::fs::chown(path, Some(uid), Some(gid)).map_err(|error| {
+            UnzipError::SetOwner {
+                path: path.to_path_buf(),
+                error,
+                span,
+            }
+        })?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, owner, span);
+    Ok(())
+}
+
+/// Accept either a single password or a list of candidates
+fn parse_passwords(value: Value) -> Result<Vec<String>, LabeledError> {
+    Ok(match value {
+        Value::List { .. } => Vec::<String>::from_value(value)?,
+        _ => vec![String::from_value(value)?],
+    })
+}
+
+/// Environment variable with a password, used without `--password` and `--password-file`
+const PASSWORD_ENV_VAR: &str = "NU_UNZIP_PASSWORD";
+
+/// Passwords from a `--password-file`, one per line
+fn read_password_file(path: &Path, span: Span) -> Result<Vec<String>, LabeledError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        LabeledError::new(format!("Fail to read password file {}", path.display()))
+            .with_label(e.to_string(), span)
+    })?;
+    Ok(contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// The directory relative paths are resolved from, given the engine's answer
+///
+/// Some embeddings can't tell the current directory, the process one is used then.
+fn current_dir(engine_dir: Result<String, ShellError>) -> Result<PathBuf, LabeledError> {
+    match engine_dir {
+        Ok(dir) => Ok(dir.into()),
+        Err(engine_error) => std::env::current_dir().map_err(|e| {
+            LabeledError::new("Fail to get the current directory").with_label(
+                format!("{engine_error}, and from the process: {e}"),
+                Span::unknown(),
+            )
+        }),
+    }
+}
+
+/// Listing rows as CSV or TSV text, with a header line of the column names
+fn delimited(rows: &[Value], separator: char) -> String {
+    fn cell(value: &Value) -> String {
+        match value {
+            Value::String { val, .. } => val.clone(),
+            // raw bytes, so that the column sorts and sums as numbers
+            Value::Filesize { val, .. } => val.get().to_string(),
+            Value::Date { val, .. } => val.to_rfc3339(),
+            Value::Int { val, .. } => val.to_string(),
+            Value::Float { val, .. } => val.to_string(),
+            Value::Bool { val, .. } => val.to_string(),
+            Value::Binary { val, .. } => val.iter().map(|byte| format!("{byte:02x}")).collect(),
+            Value::List { vals, .. } => vals.iter().map(cell).collect::<Vec<_>>().join(";"),
+            Value::Record { val, .. } => val
+                .iter()
+                .map(|(key, value)| format!("{key}={}", cell(value)))
+                .collect::<Vec<_>>()
+                .join(";"),
+            _ => String::new(),
+        }
+    }
+    let quoted = |field: String| {
+        if field.contains([separator, '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field
+        }
+    };
+    let line = |fields: Vec<String>| {
+        let fields: Vec<_> = fields.into_iter().map(quoted).collect();
+        fields.join(&separator.to_string()) + "\n"
+    };
+
+    let records: Vec<_> = rows.iter().filter_map(|row| row.as_record().ok()).collect();
+    let Some(first) = records.first() else {
+        return String::new();
+    };
+    let mut text = line(first.columns().cloned().collect());
+    for record in &records {
+        text.push_str(&line(record.values().map(cell).collect()));
+    }
+    text
+}
+
+/// Formats `--parse` turns an entry into structured data with
+const PARSE_FORMATS: [&str; 5] = ["csv", "json", "toml", "yaml", "nuon"];
+
+/// Parse the contents of an `--entry` with Nushell's `from <format>` command
+///
+/// NUON is parsed by the plugin, the other formats need their command in the engine.
+fn parse_entry(
+    engine: &nu_plugin::EngineInterface,
+    format: &Spanned<String>,
+    data: Vec<u8>,
+    span: Span,
+) -> Result<Value, LabeledError> {
+    if !PARSE_FORMATS.contains(&format.item.as_str()) {
+        return Err(
+            LabeledError::new(format!("Unknown format {}", format.item)).with_label(
+                format!("expected one of {}", PARSE_FORMATS.join(", ")),
+                format.span,
+            ),
+        );
+    }
+    let parse_error = |reason: String| {
+        LabeledError::new(format!("Fail to parse entry as {}", format.item))
+            .with_label(reason, span)
+    };
+    let text = String::from_utf8(data).map_err(|e| parse_error(e.to_string()))?;
+    if format.item == "nuon" {
+        return nuon::from_nuon(&text, Some(span)).map_err(|e| parse_error(e.to_string()));
+    }
+
+    let command = format!("from {}", format.item);
+    let decl = engine
+        .find_decl(&command)?
+        .ok_or_else(|| parse_error(format!("{command} isn't available")))?;
+    engine
+        .call_decl(
+            decl,
+            nu_plugin::EvaluatedCall::new(span),
+            PipelineData::Value(Value::string(text, span), None),
+            true,
+            false,
+        )?
+        .into_value(span)
+        .map_err(|e| parse_error(e.to_string()))
+}
+
+/// Entry names from a `--manifest-in` file, a NUON list for `.nuon` files or else one per line
+fn read_manifest(path: &Path, span: Span) -> Result<HashSet<String>, LabeledError> {
+    let manifest_error = |reason: String| {
+        LabeledError::new(format!("Fail to read manifest {}", path.display()))
+            .with_label(reason, span)
+    };
+    let contents = std::fs::read_to_string(path).map_err(|e| manifest_error(e.to_string()))?;
+    let names = if path
+        .extension()
+        .is_some_and(|extension| extension == "nuon")
+    {
+        nuon::from_nuon(&contents, Some(span))
+            .and_then(Vec::<String>::from_value)
+            .map_err(|e| manifest_error(e.to_string()))?
+    } else {
+        contents.lines().map(|line| line.to_string()).collect()
+    };
+    Ok(normalized_names(names))
+}
+
+/// Fields of one line of csv or tsv text, unquoting them as `delimited` quotes them
+fn split_delimited(line: &str, separator: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Expected CRC-32 of entries by normalized name for `--verify-list`, from a NUON table or
+/// csv/tsv text with `name` and `crc32` columns, as `--with-crc-hex` lists them
+fn read_crc_list(path: &Path, span: Span) -> Result<HashMap<String, u32>, LabeledError> {
+    let list_error = |reason: String| {
+        LabeledError::new(format!("Fail to read listing {}", path.display()))
+            .with_label(reason, span)
+    };
+    let parse_crc = |name: &str, crc: &str| {
+        u32::from_str_radix(crc.trim().trim_start_matches("0x"), 16)
+            .map_err(|e| list_error(format!("invalid crc32 {crc} for {name}: {e}")))
+    };
+    let contents = std::fs::read_to_string(path).map_err(|e| list_error(e.to_string()))?;
+    let mut crcs = HashMap::new();
+    let extension = path.extension().unwrap_or_default();
+    if extension == "nuon" {
+        let rows = nuon::from_nuon(&contents, Some(span))
+            .and_then(Vec::<Record>::from_value)
+            .map_err(|e| list_error(e.to_string()))?;
+        for row in rows {
+            let (Some(Value::String { val: name, .. }), Some(crc)) =
+                (row.get("name"), row.get("crc32"))
+            else {
+                return Err(list_error("every row needs a name and a crc32".to_string()));
+            };
+            let crc = match crc {
+                Value::Int { val, .. } => *val as u32,
+                value => parse_crc(name, &value.coerce_string()?)?,
+            };
+            crcs.insert(name.clone(), crc);
+        }
+    } else {
+        let separator = if extension == "tsv" { '\t' } else { ',' };
+        let mut lines = contents.lines();
+        let header = split_delimited(lines.next().unwrap_or_default(), separator);
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|column| column == name)
+                .ok_or_else(|| list_error(format!("no {name} column")))
+        };
+        let (name_column, crc_column) = (column("name")?, column("crc32")?);
+        for line in lines.filter(|line| !line.is_empty()) {
+            let fields = split_delimited(line, separator);
+            let (Some(name), Some(crc)) = (fields.get(name_column), fields.get(crc_column)) else {
+                return Err(list_error(format!("missing fields in {line}")));
+            };
+            crcs.insert(name.clone(), parse_crc(name, crc)?);
+        }
+    }
+    // names are compared to the normalized entry names
+    Ok(crcs
+        .into_iter()
+        .filter_map(|(name, crc)| Some((normalized_name(&name)?.to_string(), crc)))
+        .collect())
+}
+
+/// CRC-32 of the entries of the `--since-archive` reference, by normalized name
+fn read_crcs(path: &Path, span: Span) -> Result<HashMap<String, u32>, LabeledError> {
+    let source = ArchiveSource::open(path, DEFAULT_MAX_BUFFER_SIZE, None, span)?;
+    let mut archive = read_archive(source, span)?;
+    let mut crcs = HashMap::new();
+    for i in 0..archive.len() {
+        // raw, as the CRC is known without decrypting
+        let file = archive.by_index_raw(i).map_err(|e| {
+            LabeledError::new(format!("Fail to read {}", path.display()))
+                .with_label(e.to_string(), span)
+        })?;
+        if let Some(name) = normalized_name(file.name()) {
+            crcs.insert(name.to_string(), file.crc32());
+        }
+    }
+    Ok(crcs)
+}
+
+/// Entry names to select, as `--manifest-in` or `--entries-from-stdin` give them
+fn normalized_names(names: Vec<String>) -> HashSet<String> {
+    // names are compared to the normalized entry names
+    names
+        .iter()
+        .filter_map(|name| normalized_name(name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Index of the first password which decrypts entry `index` with a valid CRC
+///
+/// A wrong ZipCrypto password passes the header check about once in 256 tries,
+/// so each candidate is verified by reading the whole entry.
+fn find_password<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    passwords: &[String],
+) -> Option<usize> {
+    passwords.iter().position(|password| {
+        archive
+            .by_index_decrypt(index, password.as_bytes())
+            .and_then(|mut file| Ok(std::io::copy(&mut file, &mut std::io::sink())?))
+            .is_ok()
+    })
+}
+
+/// Declared sizes beyond this multiple of the archive data are considered crafted
+///
+/// Deflate tops out around 1032:1, other methods only get close on degenerate input.
+const MAX_PLAUSIBLE_RATIO: u64 = 1 << 16;
+
+/// Describe what is implausible about the sizes declared for an entry, if anything
+///
+/// `data_end` is where the entry data ends, i.e. the start of the central directory.
+fn implausible_size(file: &ZipFile, data_end: u64) -> Option<String> {
+    if file.size() > i64::MAX as u64 || file.compressed_size() > i64::MAX as u64 {
+        return Some("negative size".to_string());
+    }
+    if file.data_start().saturating_add(file.compressed_size()) > data_end {
+        return Some(format!(
+            "compressed size of {} bytes runs past the end of the archive",
+            file.compressed_size()
+        ));
+    }
+    if file.size() > data_end.saturating_mul(MAX_PLAUSIBLE_RATIO) {
+        return Some(format!(
+            "size of {} bytes is implausible for an archive of {data_end} bytes",
+            file.size()
+        ));
+    }
+    None
+}
+
+/// Sibling of `out_path` an entry is written to with `--atomic`
+///
+/// Renaming is only atomic within one filesystem, which a sibling in the same directory
+/// guarantees, unlike a file in the system temp directory.
+fn atomic_temp_path(out_path: &Path) -> PathBuf {
+    let name = out_path.file_name().unwrap_or_default().to_string_lossy();
+    out_path.with_file_name(format!(".{name}.{}.unzip-tmp", std::process::id()))
+}
+
+/// Whether an existing file differs from an entry, by type, size or CRC32
+fn differs_from_disk(
+    file: &ZipFile,
+    out_path: &Path,
+    metadata: &std::fs::Metadata,
+    span: Span,
+) -> Result<bool, LabeledError> {
+    if file.is_dir() || metadata.is_dir() {
+        return Ok(file.is_dir() != metadata.is_dir());
+    }
+    Ok(metadata.len() != file.size()
+        || file_crc32(out_path).map_err(|e| {
+            let out_path = out_path.to_string_lossy();
+            LabeledError::new(format!("Fail to read {out_path}")).with_label(e.to_string(), span)
+        })? != file.crc32())
+}
+
+/// All files below `dir`, without following symlinks
+fn walk_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            walk_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// The `.zip` files in `dir` for `--from-dir`, with those below it if `recursive`
+fn archives_in(dir: &Path, recursive: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if recursive {
+        walk_files(dir, &mut files)?;
+    } else {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+    });
+    files.sort();
+    Ok(files)
+}
+
+/// Directory `--into` unzips to first, next to the target so its entries can be renamed into it
+fn staging_dir(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{name}.{}.unzip-staging", std::process::id()))
+}
+
+/// Move everything unzipped to `staging` into the existing directory `target`
+///
+/// Unless `force` is given, nothing is moved if any file would be overwritten. Each file is
+/// replaced by a rename, so readers never see it partially written.
+fn merge_into(staging: &Path, target: &Path, force: bool, span: Span) -> Result<(), LabeledError> {
+    let merge_error = |e: std::io::Error| {
+        LabeledError::new(format!("Fail to merge into {}", target.display()))
+            .with_label(e.to_string(), span)
+    };
+    let mut files = Vec::new();
+    walk_files(staging, &mut files).map_err(merge_error)?;
+    if !force {
+        let mut existing: Vec<_> = files
+            .iter()
+            .filter_map(|file| file.strip_prefix(staging).ok())
+            .filter(|file| target.join(file).symlink_metadata().is_ok())
+            .map(|file| file.to_string_lossy())
+            .collect();
+        if !existing.is_empty() {
+            existing.sort();
+            return Err(
+                LabeledError::new(format!("Files already exist in {}", target.display()))
+                    .with_label(
+                        format!("{}, use --force to overwrite them", existing.join(", ")),
+                        span,
+                    ),
+            );
+        }
+    }
+    merge_dir(staging, target).map_err(merge_error)
+}
+
+fn merge_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        // directories already in the target are merged, anything else is replaced
+        if entry.file_type()?.is_dir() && dest.is_dir() {
+            merge_dir(&entry.path(), &dest)?;
+        } else {
+            std::fs::rename(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Directory holding the `--spool` directories of this plugin process
+fn spool_root() -> PathBuf {
+    std::env::temp_dir().join(format!("nu_plugin_unzip-spool-{}", std::process::id()))
+}
+
+/// Where `--verify` remembers the archives it verified, unless `--cache-dir` is given
+fn default_verify_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("nu_plugin_unzip-verify-cache")
+}
+
+/// The archive at `path` as `--verify` remembers it: a cache file named after the path,
+/// holding the path, modification time and size the archive had when it verified
+fn verify_cache_entry(cache_dir: &Path, path: &Path) -> std::io::Result<(PathBuf, String)> {
+    let path = path.canonicalize()?;
+    let metadata = std::fs::metadata(&path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let key = format!(
+        "{}\t{}\t{}",
+        path.display(),
+        modified.as_nanos(),
+        metadata.len()
+    );
+    let name = format!(
+        "{:08x}.verified",
+        crc32fast::hash(path.as_os_str().as_encoded_bytes())
+    );
+    Ok((cache_dir.join(name), key))
+}
+
+/// Create a fresh directory below [`spool_root`] for one `--spool` run
+fn create_spool_dir(span: Span) -> UnzipResult<PathBuf> {
+    static NEXT_SPOOL: AtomicUsize = AtomicUsize::new(0);
+    let dir = spool_root().join(NEXT_SPOOL.fetch_add(1, Ordering::Relaxed).to_string());
+    std::fs::create_dir_all(&dir).map_err(|error| UnzipError::Create {
+        path: dir.clone(),
+        error,
+        span,
+    })?;
+    Ok(dir)
+}
+
+/// Write what can still be decompressed of a damaged entry to `<name>.partial` in `dir`, for
+/// `--repair`, returning the file and how many bytes it got
+///
+/// Entries that can't be opened at all, or whose name escapes `dir`, get no file.
+fn salvage_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    name: &str,
+    dir: &Path,
+) -> Option<(PathBuf, u64)> {
+    let mut file = archive.by_index(index).ok()?;
+    let path = enclosed_path(name)?;
+    let partial = dir.join(format!("{}.partial", path.to_string_lossy()));
+    if let Some(parent) = partial.parent() {
+        std::fs::create_dir_all(parent).ok()?;
+    }
+    let mut output = std::fs::File::create(&partial).ok()?;
+    let mut recovered = 0;
+    let mut buffer = [0; 1024];
+    // the data read before the error, a CRC mismatch only shows at the end, is kept
+    while let Ok(bytes_read @ 1..) = file.read(&mut buffer) {
+        if output.write_all(&buffer[..bytes_read]).is_err() {
+            break;
+        }
+        recovered += bytes_read as u64;
+    }
+    Some((partial, recovered))
+}
+
+/// Create a new directory for `--tmp`, in `/dev/shm` on Linux as it's kept in memory, or else
+/// in the temp directory (`$TMPDIR` on unix)
+///
+/// Unlike the spool, these directories are left for the caller to remove.
+fn create_tmp_dir(span: Span) -> UnzipResult<PathBuf> {
+    static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+    // pids are reused, so the time tells runs of different processes apart
+    let name = format!(
+        "nu_plugin_unzip-{}-{}-{}",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    );
+    let mut roots = Vec::new();
+    if cfg!(target_os = "linux") && Path::new("/dev/shm").is_dir() {
+        roots.push(PathBuf::from("/dev/shm"));
+    }
+    roots.push(std::env::temp_dir());
+
+    let mut last_error = None;
+    for root in roots {
+        let dir = root.join(&name);
+        // /dev/shm may be read-only, e.g. in containers
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(error) => {
+                last_error = Some(UnzipError::Create {
+                    path: dir,
+                    error,
+                    span,
+                })
+            }
+        }
+    }
+    Err(last_error.expect("the temp directory is always tried"))
+}
+
+/// Remove the `--spool` directories of this plugin process, done by `unzip clean` and when
+/// the plugin exits
+pub fn remove_spool() -> std::io::Result<()> {
+    match std::fs::remove_dir_all(spool_root()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// CRC32 of a file on disk
+fn file_crc32(path: &Path) -> std::io::Result<u32> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Error for `--fail-if-empty` when nothing of the archive at `path` is selected
+fn no_entries_error(action: &str, path: &Path, span: Span) -> LabeledError {
+    LabeledError::new(format!("No entries to {action} in {}", path.display())).with_label(
+        "--fail-if-empty is set, check the archive and the filters",
+        span,
+    )
+}
+
+/// Check that a user supplied prefix only consists of plain path components, so that it can't
+/// move entries outside the target directory
+fn check_prefix(prefix: &str, span: Span) -> Result<PathBuf, LabeledError> {
+    let mut path = PathBuf::new();
+    for component in Path::new(prefix).components() {
+        match component {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            _ => {
+                return Err(LabeledError::new(format!("Invalid prefix {prefix}"))
+                    .with_label("prefix must be a relative path without '..'", span))
+            }
+        }
+    }
+    Ok(path)
+}
+
+impl UnzipCommand {
+    /// Describe `archive` as one record with its entries nested by directory, for `--to-json`
+    fn manifest<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        path: &Path,
+        filter: &EntryFilter,
+        log: &mut Log,
+    ) -> Value {
+        let mut tree = ManifestDir::default();
+        let mut stats = CompressionStats::default();
+        for i in 0..archive.len() {
+            let file = match archive.by_index_raw(i) {
+                Ok(file) => file,
+                Err(e) => {
+                    log.warn(format!("Skipping entry #{i}: {e}"));
+                    continue;
+                }
+            };
+            let file_name = match normalized_name(file.name()) {
+                Some(name) if filter.matches(name, &file, log) => name,
+                _ => continue,
+            };
+            if file.is_dir() {
+                tree.insert(file_name, None);
+                continue;
+            }
+            stats.add(&file);
+
+            let mut record = Record::default();
+            let base_name = file_name.rsplit('/').next().unwrap_or(file_name);
+            record.push("name", Value::string(base_name, span));
+            record.push("type", Value::string("file", span));
+            record.push("size", Value::filesize(file.size() as i64, span));
+            record.push(
+                "compressed_size",
+                Value::filesize(file.compressed_size() as i64, span),
+            );
+            record.push(
+                "method",
+                Value::string(method_name(file.compression()), span),
+            );
+            record.push("crc32", Value::int(file.crc32() as i64, span));
+            record.push("encryption", Value::string(entry_encryption(&file), span));
+            record.push("modified", Value::date(entry_modified(&file).into(), span));
+            tree.insert(file_name, Some(Value::record(record, span)));
+        }
+
+        let mut record = Record::default();
+        record.push("path", Value::string(path.to_string_lossy(), span));
+        record.push(
+            "comment",
+            Value::string(String::from_utf8_lossy(archive.comment()), span),
+        );
+        record.push("files", Value::int(stats.entries as i64, span));
+        record.push(
+            "size",
+            Value::filesize(stats.total_uncompressed as i64, span),
+        );
+        record.push(
+            "compressed_size",
+            Value::filesize(stats.total_compressed as i64, span),
+        );
+        record.push("children", tree.into_children(span));
+        Value::record(record, span)
+    }
+
+    fn list_files<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        raw: &mut R,
+        options: &ListOptions,
+        source: Option<&str>,
+        log: &mut Log,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let mut rows = Vec::new();
+        for i in 0..archive.len() {
+            // only metadata is needed, so encrypted entries can be listed without a password
+            let file = match archive.by_index_raw(i) {
+                Ok(file) => file,
+                Err(e) => {
+                    log.warn(format!("Skipping entry #{i}: {e}"));
+                    continue;
+                }
+            };
+            let Some(mut row) = options.row(&file, span, log) else {
+                continue;
+            };
+            let file_name = file.name();
+            if options.verbose {
+                let central = CentralHeader::read(raw, file.central_header_start());
+                match &central {
+                    Ok(header) => {
+                        row.push(
+                            "made_by_version",
+                            Value::string(header.made_by_version(), span),
+                        );
+                        row.push("host_os", Value::string(header.host_os(), span));
+                        row.push(
+                            "version_needed",
+                            Value::string(header.version_needed(), span),
+                        );
+                        let attributes = header
+                            .dos_attributes()
+                            .into_iter()
+                            .map(|name| Value::string(name, span))
+                            .collect();
+                        row.push("attributes", Value::list(attributes, span));
+                        row.push("flags", header.flags(span));
+                    }
+                    Err(e) => {
+                        log.warn(format!("Fail to read central header of {file_name}: {e}"));
+                        row.push("made_by_version", Value::nothing(span));
+                        row.push("host_os", Value::nothing(span));
+                        row.push("version_needed", Value::nothing(span));
+                        row.push("attributes", Value::nothing(span));
+                        row.push("flags", Value::nothing(span));
+                    }
+                }
+                let consistent = match (&central, LocalHeader::read(raw, file.header_start())) {
+                    (Ok(central), Ok(local)) => {
+                        let mismatches = central.mismatches(&local, file.name_raw());
+                        if !mismatches.is_empty() {
+                            log.warn(format!(
+                                "{file_name}: local header disagrees with the central directory \
+                                 on {}",
+                                mismatches.join(", ")
+                            ));
+                        }
+                        Value::bool(mismatches.is_empty(), span)
+                    }
+                    (Ok(_), Err(e)) => {
+                        log.warn(format!("Fail to read local header of {file_name}: {e}"));
+                        Value::nothing(span)
+                    }
+                    (Err(_), _) => Value::nothing(span),
+                };
+                row.push("header_consistent", consistent);
+                row.push("encryption", Value::string(entry_encryption(&file), span));
+                row.push(
+                    "compression",
+                    Value::string(method_name(file.compression()), span),
+                );
+                row.push(
+                    "unsafe_name",
+                    Value::bool(is_unsafe_name(file.name()), span),
+                );
+                // the name as stored, for names the decoder mangles
+                row.push("raw_name", Value::binary(file.name_raw(), span));
+                row.push("custom_fields", custom_fields(&file, span));
+            }
+            if let Some(size) = options.preview {
+                let is_dir = file.is_dir();
+                drop(file);
+                // unlike the metadata, this needs the data decompressed
+                let value = match archive.by_index(i) {
+                    Ok(_) if is_dir => Value::nothing(span),
+                    Ok(file) => preview(file, size, span).unwrap_or_else(|e| {
+                        log.warn(format!("Fail to preview entry #{i}: {e}"));
+                        Value::nothing(span)
+                    }),
+                    Err(e) => {
+                        log.warn(format!("Fail to preview entry #{i}: {e}"));
+                        Value::nothing(span)
+                    }
+                };
+                row.push("preview", value);
+            }
+            if let Some(source) = source {
+                row.push("source", Value::string(source, span));
+            }
+
+            rows.push(Value::record(row, span));
+        }
+
+        Ok(rows)
+    }
+
+    /// Decompress every entry of `archive` to check its CRC32, returning how many were checked
+    /// and a `{name, error}` record for each that failed
+    fn verify<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        repair_dir: Option<&Path>,
+        log: &mut Log,
+    ) -> (usize, Vec<Value>) {
+        let mut checked = 0;
+        let mut failed = Vec::new();
+        for i in 0..archive.len() {
+            let encrypted = archive.by_index_raw(i).is_ok_and(|file| file.encrypted());
+            let name = archive.name_for_index(i).unwrap_or_default().to_string();
+            if encrypted {
+                log.warn(format!("Not verifying {name}: it is encrypted"));
+                continue;
+            }
+            let error = match archive.by_index(i) {
+                Ok(mut file) => match std::io::copy(&mut file, &mut std::io::sink()) {
+                    Ok(_) => None,
+                    Err(error) => {
+                        let message = error.to_string();
+                        match UnzipError::read_entry(name.clone(), error, span) {
+                            UnzipError::Corrupt { .. } => {
+                                Some("data corruption (CRC mismatch)".to_string())
+                            }
+                            _ => Some(message),
+                        }
+                    }
+                },
+                Err(e) => Some(e.to_string()),
+            };
+            checked += 1;
+            if let Some(error) = error {
+                log.warn(format!("{name} failed to verify: {error}"));
+                let mut row = Record::default();
+                row.push("name", Value::string(name.clone(), span));
+                row.push("error", Value::string(error, span));
+                if let Some(dir) = repair_dir {
+                    let (partial, recovered) = match salvage_entry(archive, i, &name, dir) {
+                        Some((partial, recovered)) => (
+                            Value::string(partial.to_string_lossy(), span),
+                            Value::filesize(recovered as i64, span),
+                        ),
+                        None => (Value::nothing(span), Value::nothing(span)),
+                    };
+                    row.push("partial", partial);
+                    row.push("recovered", recovered);
+                }
+                failed.push(Value::record(row, span));
+            }
+        }
+        (checked, failed)
+    }
+
+    /// List entries from their local file headers instead of the central directory, for
+    /// `--local`, which means decompressing the whole archive from start to end
+    fn list_local(
+        &self,
+        span: Span,
+        reader: &mut impl Read,
+        options: &ListOptions,
+        source: Option<&str>,
+        log: &mut Log,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let mut rows = Vec::new();
+        loop {
+            let mut file = match zip::read::read_zipfile_from_stream(reader) {
+                Ok(Some(file)) => file,
+                Ok(None) => break,
+                Err(e) => {
+                    return Err(UnzipError::Read {
+                        reason: e.to_string(),
+                        span,
+                    }
+                    .into())
+                }
+            };
+            let Some(mut row) = options.row(&file, span, log) else {
+                continue;
+            };
+            // entries followed by a data descriptor have zero sizes and CRC32 here
+            row.push(
+                "compressed_size",
+                Value::filesize(file.compressed_size() as i64, span),
+            );
+            row.push("crc32", Value::int(file.crc32() as i64, span));
+            if let Some(size) = options.preview.filter(|_| !file.is_dir()) {
+                let value = preview(&mut file, size, span).unwrap_or_else(|e| {
+                    log.warn(format!("Fail to preview {}: {e}", file.name()));
+                    Value::nothing(span)
+                });
+                row.push("preview", value);
+            } else if options.preview.is_some() {
+                row.push("preview", Value::nothing(span));
+            }
+            if let Some(source) = source {
+                row.push("source", Value::string(source, span));
+            }
+            rows.push(Value::record(row, span));
+        }
+        Ok(rows)
+    }
+
+    /// Table of entries that would overwrite existing paths in `dir`
+    fn find_conflicts<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        options: &ExtractOptions,
+        dir: &Path,
+    ) -> Result<Vec<Value>, LabeledError> {
+        // entries that are skipped are reported when extracting
+        let mut log = Log {
+            quiet: true,
+            ..Default::default()
+        };
+
+        let mut conflicts = Vec::new();
+        for i in 0..archive.len() {
+            let Ok(file) = archive.by_index(i) else {
+                continue;
+            };
+            let Ok(out_path) = options.out_path(&file, dir, &mut log) else {
+                continue;
+            };
+            let Ok(metadata) = std::fs::symlink_metadata(&out_path) else {
+                continue;
+            };
+
+            let differs = differs_from_disk(&file, &out_path, &metadata, span)?;
+
+            let mut row = Record::default();
+            row.push("name", Value::string(file.name(), span));
+            row.push("path", Value::string(out_path.to_string_lossy(), span));
+            row.push("size", Value::filesize(file.size() as i64, span));
+            row.push(
+                "existing_size",
+                Value::filesize(metadata.len() as i64, span),
+            );
+            row.push("differs", Value::bool(differs, span));
+            conflicts.push(Value::record(row, span));
+        }
+
+        Ok(conflicts)
+    }
+
+    /// Bytes of the entry `name` within `range`, or all of them
+    ///
+    /// Compressed entries can't be read at random, so everything before the range is
+    /// decompressed and thrown away, but nothing after it is read.
+    fn read_range<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        name: &str,
+        range: Option<&IntRange>,
+    ) -> Result<Vec<u8>, LabeledError> {
+        let read_error = |e: std::io::Error| {
+            LabeledError::new(format!("Fail to read entry {name}")).with_label(e.to_string(), span)
+        };
+        let mut file = archive.by_name(name).map_err(|e| {
+            LabeledError::new(format!("Fail to read entry {name}")).with_label(e.to_string(), span)
+        })?;
+        let size = file.size();
+
+        let (start, end) = match range {
+            Some(range) => {
+                let end = match range.end() {
+                    Bound::Included(end) => end.saturating_add(1),
+                    Bound::Excluded(end) => end,
+                    Bound::Unbounded => size as i64,
+                };
+                if range.start() < 0 || end as u64 > size || range.start() > end {
+                    return Err(LabeledError::new(format!(
+                        "Range {range} is outside of entry {name}"
+                    ))
+                    .with_label(format!("The entry is {size} bytes long"), span));
+                }
+                (range.start() as u64, end as u64)
+            }
+            None => (0, size),
+        };
+
+        std::io::copy(&mut file.by_ref().take(start), &mut std::io::sink()).map_err(read_error)?;
+        let mut data = vec![0; (end - start) as usize];
+        file.read_exact(&mut data).map_err(read_error)?;
+        Ok(data)
+    }
+
+    /// Write the selected entries of all `archives` as one tar stream to `writer`
+    fn write_tar<W: Write>(
+        &self,
+        span: Span,
+        archives: &mut [ZipArchive<ArchiveSource>],
+        filter: &EntryFilter,
+        writer: W,
+        log: &mut Log,
+    ) -> Result<W, LabeledError> {
+        let mut builder = tar::Builder::new(writer);
+        for archive in archives {
+            for i in 0..archive.len() {
+                let file = match archive.by_index(i) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        log.warn(format!("Skipping entry #{i}: {e}"));
+                        continue;
+                    }
+                };
+                let Some(name) = normalized_name(file.name()) else {
+                    continue;
+                };
+                if !filter.matches(name, &file, log) {
+                    continue;
+                }
+                // the same paths unzipping would write
+                let Some(path) = enclosed_path(file.name()) else {
+                    log.warn(format!("Skipping {name}: path is outside of the archive"));
+                    continue;
+                };
+
+                let mut header = tar::Header::new_gnu();
+                let default_mode = if file.is_dir() { 0o755 } else { 0o644 };
+                header.set_mode(file.unix_mode().map_or(default_mode, |mode| mode & 0o7777));
+                header.set_mtime(entry_modified(&file).timestamp().max(0) as u64);
+                if file.is_dir() {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_size(0);
+                } else {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(file.size());
+                }
+                let name = name.to_string();
+                builder
+                    .append_data(&mut header, path, file)
+                    .map_err(|error| UnzipError::read_entry(name, error, span))?;
+            }
+        }
+        builder.into_inner().map_err(|e| {
+            LabeledError::new("Fail to write tar file").with_label(e.to_string(), span)
+        })
+    }
+
+    /// Paths and contents of the selected file entries, as strings when they are UTF-8,
+    /// erroring once `total_read` gets over `max_size`
+    fn read_entries<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        filter: &EntryFilter,
+        max_size: u64,
+        total_read: &mut u64,
+        log: &mut Log,
+    ) -> Result<Vec<(String, Value)>, LabeledError> {
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = match archive.by_index(i) {
+                Ok(file) => file,
+                Err(e) => {
+                    log.warn(format!("Skipping entry #{i}: {e}"));
+                    continue;
+                }
+            };
+            let name = match normalized_name(file.name()) {
+                Some(name) if !file.is_dir() => name.to_string(),
+                _ => continue,
+            };
+            if !filter.matches(&name, &file, log) {
+                continue;
+            }
+
+            // read one byte past the limit to tell if the entry exceeds it
+            let mut data = Vec::new();
+            file.by_ref()
+                .take(max_size.saturating_sub(*total_read) + 1)
+                .read_to_end(&mut data)
+                .map_err(|error| UnzipError::read_entry(name.clone(), error, span))?;
+            *total_read += data.len() as u64;
+            if *total_read > max_size {
+                return Err(UnzipError::TooLarge { max_size, span }.into());
+            }
+            let value = match String::from_utf8(data) {
+                Ok(text) => Value::string(text, span),
+                Err(e) => Value::binary(e.into_bytes(), span),
+            };
+            entries.push((name, value));
+        }
+        Ok(entries)
+    }
+
+    /// Classify entries as added, changed or same compared to the files in `dir`
+    ///
+    /// Paths the entries map to are collected in `seen` to find removed files afterwards.
+    fn compare_entries<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        options: &ExtractOptions,
+        dir: &Path,
+        seen: &mut HashSet<PathBuf>,
+        log: &mut Log,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let mut rows = Vec::new();
+        for i in 0..archive.len() {
+            let file = match archive.by_index_raw(i) {
+                Ok(file) => file,
+                Err(e) => {
+                    log.warn(format!("Skipping entry #{i}: {e}"));
+                    continue;
+                }
+            };
+            let Ok(out_path) = options.out_path(&file, dir, log) else {
+                continue;
+            };
+
+            let status = match std::fs::symlink_metadata(&out_path) {
+                Err(_) => "added",
+                Ok(metadata) if differs_from_disk(&file, &out_path, &metadata, span)? => "changed",
+                Ok(_) => "same",
+            };
+
+            let mut row = Record::default();
+            row.push("name", Value::string(file.name(), span));
+            row.push("path", Value::string(out_path.to_string_lossy(), span));
+            row.push("status", Value::string(status, span));
+            rows.push(Value::record(row, span));
+            seen.insert(out_path);
+        }
+
+        Ok(rows)
+    }
+
+    /// Unzip all entries into `dir`, returning the `--report-conflicts` table if requested
+    ///
+    /// With a resume file, entries listed in it are skipped and the others are added to it
+    /// once unzipped. It is removed when all entries are done.
+    fn unzip_file<R: Read + Seek>(
+        &self,
+        span: Span,
+        archive: &mut ZipArchive<R>,
+        options: &ExtractOptions,
+        dir: &Path,
+        run: &ArchiveRun,
+        log: &mut Log,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let resume_file = run.resume_file.as_deref();
+        check_entry_count(archive.len(), options.max_entries, span)?;
+        if let Some(encoding) = options
+            .out_encoding
+            .as_ref()
+            .filter(|encoding| encoding.unmappable == Unmappable::Error)
+        {
+            if let Some(name) = archive
+                .file_names()
+                .find(|name| encoding.encode(name).is_none())
+            {
+                return Err(LabeledError::new(format!(
+                    "{name} can't be written in {}",
+                    encoding.encoding.name()
+                ))
+                .with_label("Use --unmappable skip or replace to unzip it anyway", span));
+            }
+        }
+
+        // streamed archives have no end to check sizes against, --strict buffers them
+        let data_end = archive.central_directory_start();
+        for i in 0..archive.len() {
+            let Ok(file) = archive.by_index_raw(i) else {
+                continue;
+            };
+            check_entry(&file, data_end, options.strict, span, log)?;
+        }
+
+        let conflicts = if options.report_conflicts {
+            let conflicts = self.find_conflicts(span, archive, options, dir)?;
+            if !conflicts.is_empty() && !options.force {
+                return Ok(conflicts);
+            }
+            conflicts
+        } else {
+            Vec::new()
+        };
+
+        let resume_error = |path: &Path, e: std::io::Error| {
+            LabeledError::new(format!("Fail to update resume file {}", path.display()))
+                .with_label(e.to_string(), span)
+        };
+        let (completed, mut resume_log) = match resume_file {
+            Some(path) => {
+                let completed = read_resume_file(path).map_err(|e| resume_error(path, e))?;
+                let resume_log = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| resume_error(path, e))?;
+                (completed, Some(resume_log))
+            }
+            None => (HashSet::new(), None),
+        };
+
+        let mut state = ExtractState::default();
+        for i in 0..archive.len() {
+            options.check_deadline(&state, span)?;
+            // owned, as the archive stays borrowed by the entry being read
+            let name = archive.name_for_index(i).unwrap_or_default().to_string();
+            if completed.contains(&i) {
+                log.debug(format!("Skipping entry #{i}: already unzipped"));
+                // not a skip for --report, the entry is unzipped all the same
+                options.emit("skip", &name, 0, span);
+                continue;
+            }
+            if run.hidden.contains(&i) {
+                log.debug(format!("Skipping entry #{i}: hidden or system"));
+                options.skip(SkipReason::Filtered, &name, span);
+                continue;
+            }
+            let (encrypted, method) = match archive.by_index_raw(i) {
+                Ok(file) => (file.encrypted(), Some(file.compression())),
+                Err(_) => (false, None),
+            };
+            let file = if encrypted && !options.passwords.is_empty() {
+                match find_password(archive, i, &options.passwords) {
+                    Some(n) => {
+                        log.debug(format!("Decrypting entry #{i} with password #{n}"));
+                        archive.by_index_decrypt(i, options.passwords[n].as_bytes())
+                    }
+                    None => Err(ZipError::InvalidPassword),
+                }
+            } else {
+                archive.by_index(i)
+            };
+            let mut file = match file {
+                Ok(file) => file,
+                Err(e) => {
+                    // encrypted entries without a password also fail as unsupported
+                    let reason = match (&e, method) {
+                        _ if encrypted => {
+                            log.warn(format!("Skipping entry #{i}: {e}"));
+                            SkipReason::Encrypted
+                        }
+                        (ZipError::UnsupportedArchive(_), Some(method)) => {
+                            log.warn(format!(
+                                "Skipping entry #{i}: {e}, this build can't decompress {}",
+                                method_name(method)
+                            ));
+                            SkipReason::Unsupported
+                        }
+                        _ => {
+                            log.warn(format!("Skipping entry #{i}: {e}"));
+                            SkipReason::Other
+                        }
+                    };
+                    options.skip(reason, &name, span);
+                    continue;
+                }
+            };
+            let out_path = match options.out_path(&file, dir, log) {
+                Ok(path) => path,
+                Err(reason) => {
+                    options.skip(reason, file.name(), span);
+                    continue;
+                }
+            };
+            // a renamed entry leaves the archive alone
+            if options.rename.is_none() && run.overwrites_archive(&out_path) {
+                log.warn(format!(
+                    "Skipping {}: it would overwrite the archive being unzipped, {}",
+                    file.name(),
+                    out_path.display()
+                ));
+                options.skip(SkipReason::Unsafe, file.name(), span);
+                continue;
+            }
+            if run.reparse.contains(&i) {
+                if cfg!(windows) {
+                    match create_reparse_point(&mut file, &out_path, options.force, log) {
+                        Ok(()) => state.extracted += 1,
+                        Err(reason) => options.skip(reason, file.name(), span),
+                    }
+                    continue;
+                }
+                log.warn(format!(
+                    "Writing {} as a plain file: reparse points are only created on Windows",
+                    file.name()
+                ));
+            }
+
+            match self
+                .extract_entry_with_events(span, &mut file, &out_path, options, &mut state, log)
+            {
+                Err(UnzipError::Corrupt { name, .. }) if options.skip_errors => {
+                    log.warn(format!("Skipping {name}: data corruption (CRC mismatch)"));
+                    options.report.borrow_mut().add(SkipReason::Corrupt);
+                    continue;
+                }
+                result => result?,
+            }
+            if let (Some(resume_log), Some(path)) = (&mut resume_log, resume_file) {
+                writeln!(resume_log, "{i}").map_err(|e| resume_error(path, e))?;
+            }
+        }
+        log.debug(format!(
+            "Extracted {} entries, {} bytes in {:.2?}, {}",
+            state.extracted,
+            state.total_written,
+            state.elapsed,
+            throughput(state.total_written, state.elapsed)
+        ));
+        options.report.borrow_mut().extracted += state.extracted;
+
+        if let Some(path) = resume_file {
+            drop(resume_log);
+            std::fs::remove_file(path).map_err(|e| resume_error(path, e))?;
+        }
+
+        Ok(conflicts)
+    }
+
+    /// [`Self::extract_entry`], reporting its start and outcome with `--events`
+    fn extract_entry_with_events(
+        &self,
+        span: Span,
+        file: &mut ZipFile,
+        out_path: &Path,
+        options: &ExtractOptions,
+        state: &mut ExtractState,
+        log: &mut Log,
+    ) -> UnzipResult<()> {
+        options.emit("start", file.name(), 0, span);
+        let written = state.total_written;
+        let started = Instant::now();
+        let result = self.extract_entry(span, file, out_path, options, state, log);
+        let elapsed = started.elapsed();
+        state.elapsed += elapsed;
+        let event = if result.is_ok() { "done" } else { "error" };
+        options.emit(event, file.name(), state.total_written - written, span);
+        if result.is_ok() && !file.is_dir() {
+            let bytes = state.total_written - written;
+            log.debug(format!(
+                "Extracted {bytes} bytes of {} in {elapsed:.2?}, {}",
+                file.name(),
+                throughput(bytes, elapsed)
+            ));
+        }
+        result
+    }
+
+    /// Write a single entry to `out_path`
+    fn extract_entry(
+        &self,
+        span: Span,
+        file: &mut ZipFile,
+        out_path: &Path,
+        options: &ExtractOptions,
+        state: &mut ExtractState,
+        log: &mut Log,
+    ) -> UnzipResult<()> {
+        let renamed;
+        let out_path = match &options.rename {
+            Some(format) if !file.is_dir() && out_path.symlink_metadata().is_ok() => {
+                renamed = format.free_path(out_path);
+                log.debug(format!(
+                    "Renaming {} to {} as it exists",
+                    out_path.display(),
+                    renamed.display()
+                ));
+                &renamed
+            }
+            _ => out_path,
+        };
+        log.debug(format!("Extracting {}", out_path.display()));
+
+        if out_path.exists() && !options.force {
+            return Err(UnzipError::Exists {
+                path: out_path.to_path_buf(),
+                span,
+            });
+        }
+
+        let owner = options.owner(file);
+        if file.is_dir() {
+            std::fs::create_dir_all(out_path).map_err(|error| UnzipError::Create {
+                path: out_path.to_path_buf(),
+                error,
+                span,
+            })?;
+            set_mode(out_path, options.dir_mode, span)?;
+            set_owner(out_path, owner, span)?;
+        } else {
+            // are all directories already created ?
+            if let Some(out_dir) = out_path.parent() {
+                std::fs::create_dir_all(out_dir).map_err(|error| UnzipError::Create {
+                    path: out_dir.to_path_buf(),
+                    error,
+                    span,
+                })?;
+            }
+
+            // empty entries and those without a known size, as when streaming, aren't deduped
+            let dedupe_key =
+                (options.dedupe && file.size() > 0).then(|| (file.crc32(), file.size()));
+            if let Some(original) = dedupe_key.and_then(|key| state.written.get(&key)) {
+                match link_or_copy(original, out_path) {
+                    Ok(()) => {
+                        log.debug(format!(
+                            "Linked {} to {}",
+                            out_path.display(),
+                            original.display()
+                        ));
+                        set_mode(out_path, options.file_mode, span)?;
+                        options.unzipped(file, out_path);
+                        state.extracted += 1;
+                        return Ok(());
+                    }
+                    Err(e) => log.debug(format!(
+                        "Fail to link {} to {}: {e}",
+                        out_path.display(),
+                        original.display()
+                    )),
+                }
+            }
+
+            let mut destination = FileDestination {
+                fsync: options.fsync,
+            };
+            if options.atomic {
+                let temp_path = atomic_temp_path(out_path);
+                self.write_entry(span, file, &temp_path, &mut destination, options, state)
+                    .and_then(|()| {
+                        std::fs::rename(&temp_path, out_path).map_err(|error| UnzipError::Rename {
+                            path: out_path.to_path_buf(),
+                            error,
+                            span,
+                        })
+                    })
+                    .inspect_err(|_| {
+                        let _ = std::fs::remove_file(&temp_path);
+                    })?;
+            } else {
+                self.write_entry(span, file, out_path, &mut destination, options, state)
+                    .inspect_err(|error| {
+                        // don't leave a truncated file behind, unless it couldn't even be
+                        // created and may be an existing one
+                        if !matches!(error, UnzipError::Create { .. }) {
+                            let _ = std::fs::remove_file(out_path);
+                        }
+                    })?;
+            }
+            set_mode(out_path, options.file_mode, span)?;
+            set_owner(out_path, owner, span)?;
+
+            if let Some(key) = dedupe_key {
+                state
+                    .written
+                    .entry(key)
+                    .or_insert_with(|| out_path.to_path_buf());
+            }
+            if let Some(sizes) = &options.sizes {
+                sizes
+                    .borrow_mut()
+                    .push((out_path.to_path_buf(), file.size()));
+            }
+            options.unzipped(file, out_path);
+        }
+
+        state.extracted += 1;
+        Ok(())
+    }
+
+    /// Copy the contents of an entry to the writer `destination` creates for `path`
+    fn write_entry(
+        &self,
+        span: Span,
+        file: &mut ZipFile,
+        path: &Path,
+        destination: &mut dyn EntryDestination,
+        options: &ExtractOptions,
+        state: &mut ExtractState,
+    ) -> UnzipResult<()> {
+        let write_error = |error| UnzipError::Write {
+            path: path.to_path_buf(),
+            error,
+            span,
+        };
+        let mut output_file = destination
+            .create(path)
+            .map_err(|error| UnzipError::Create {
+                path: path.to_path_buf(),
+                error,
+                span,
+            })?;
+        if options.strip_bom {
+            let mut prefix = Vec::with_capacity(TEXT_SNIFF_SIZE);
+            (&mut *file)
+                .take(TEXT_SNIFF_SIZE as u64)
+                .read_to_end(&mut prefix)
+                .map_err(|error| UnzipError::read_entry(file.name().to_string(), error, span))?;
+            state.total_written += prefix.len() as u64;
+            if let Some(max_size) = options.max_size {
+                if state.total_written > max_size {
+                    return Err(UnzipError::TooLarge { max_size, span });
+                }
+            }
+            output_file
+                .write_all(&prefix[text_bom_len(&prefix)..])
+                .map_err(write_error)?;
+        }
+        let mut buffer = [0; 1024];
+        loop {
+            let bytes_read = file
+                .read(&mut buffer)
+                .map_err(|error| UnzipError::read_entry(file.name().to_string(), error, span))?;
+            if bytes_read == 0 {
+                break;
+            }
+            // a single entry can take arbitrarily long to decompress
+            options.check_deadline(state, span)?;
+            state.total_written += bytes_read as u64;
+            if let Some(max_size) = options.max_size {
+                if state.total_written > max_size {
+                    return Err(UnzipError::TooLarge { max_size, span });
+                }
+            }
+            output_file
+                .write_all(&buffer[0..bytes_read])
+                .map_err(write_error)?;
+        }
+        output_file.flush().map_err(write_error)
+    }
+
+    /// Unzip entries one after another from a reader that can't seek, using only the local
+    /// file headers
+    fn unzip_stream(
+        &self,
+        span: Span,
+        reader: &mut impl Read,
+        options: &ExtractOptions,
+        dir: &Path,
+        log: &mut Log,
+    ) -> Result<(), LabeledError> {
+        let mut state = ExtractState::default();
+        let mut entries = 0;
+        loop {
+            options.check_deadline(&state, span)?;
+            let mut file = match zip::read::read_zipfile_from_stream(reader) {
+                Ok(Some(file)) => file,
+                Ok(None) => break,
+                Err(e) => {
+                    return Err(UnzipError::Read {
+                        reason: e.to_string(),
+                        span,
+                    }
+                    .into())
+                }
+            };
+            // without a central directory the entries can only be counted as they come
+            entries += 1;
+            check_entry_count(entries, options.max_entries, span)?;
+            let out_path = match options.out_path(&file, dir, log) {
+                Ok(path) => path,
+                Err(reason) => {
+                    options.skip(reason, file.name(), span);
+                    continue;
+                }
+            };
+
+            match self
+                .extract_entry_with_events(span, &mut file, &out_path, options, &mut state, log)
+            {
+                Err(UnzipError::Corrupt { name, .. }) if options.skip_errors => {
+                    log.warn(format!("Skipping {name}: data corruption (CRC mismatch)"));
+                    options.report.borrow_mut().add(SkipReason::Corrupt);
+                }
+                result => result?,
+            }
+        }
+        log.debug(format!(
+            "Extracted {} entries, {} bytes in {:.2?}, {}",
+            state.extracted,
+            state.total_written,
+            state.elapsed,
+            throughput(state.total_written, state.elapsed)
+        ));
+        options.report.borrow_mut().extracted += state.extracted;
+
+        Ok(())
+    }
+
+    /// Unzip each archive into `dir`, returning the `--report-conflicts` table and the
+    /// `--recursive-zips` tree
+    fn unzip_archives(
+        &self,
+        span: Span,
+        zip_file_paths: &[PathBuf],
+        options: &ExtractOptions,
+        dir: &Path,
+        max_buffer_size: u64,
+        log: &mut Log,
+    ) -> Result<(Vec<Value>, Vec<Value>), LabeledError> {
+        let mut conflicts = Vec::new();
+        let mut nested = Vec::new();
+        for zip_file_path in zip_file_paths {
+            let extracted = options.report.borrow().extracted;
+            let check_empty = || {
+                if options.fail_if_empty && options.report.borrow().extracted == extracted {
+                    return Err(no_entries_error("unzip", zip_file_path, span));
+                }
+                Ok(())
+            };
+            // unless going over the entries twice, as for a conflict report, root detection or
+            // refusing an archive with --strict or --unmappable error before unzipping any of
+            // it, non-seekable files are unzipped as they're read instead of buffering them
+            let two_passes = options.report_conflicts
+                || options.strict
+                || options
+                    .out_encoding
+                    .as_ref()
+                    .is_some_and(|encoding| encoding.unmappable == Unmappable::Error)
+                || options.keep_root
+                || options.resume
+                || options.latest_only
+                || options.filter.names.is_some()
+                || options.nested_depth.is_some()
+                || options.offset.is_some()
+                || options.skip_hidden
+                || options.allow_reparse;
+            if options.data.is_none() && !two_passes && !is_seekable(zip_file_path) {
+                let mut zip_file = open_zip_file(zip_file_path, span)?;
+                self.unzip_stream(span, &mut zip_file, options, dir, log)?;
+                check_empty()?;
+                continue;
+            }
+
+            let source = match &options.data {
+                Some(data) => ArchiveSource::from_data(data, options.offset, span)?,
+                None => ArchiveSource::open(zip_file_path, max_buffer_size, options.offset, span)?,
+            };
+            let mut raw = source.try_clone(span)?;
+            let mut archive = read_archive(source, span)?;
+            if let Some(names) = &options.filter.names {
+                let entries: HashSet<_> =
+                    archive.file_names().filter_map(normalized_name).collect();
+                let mut missing: Vec<_> = names
+                    .iter()
+                    .filter(|name| !entries.contains(name.as_str()))
+                    .map(|name| name.as_str())
+                    .collect();
+                missing.sort();
+                if !missing.is_empty() {
+                    let missing = missing.join(", ");
+                    if !options.skip_errors {
+                        return Err(LabeledError::new(format!(
+                            "Manifest entries not found in {}",
+                            zip_file_path.display()
+                        ))
+                        .with_label(missing, span));
+                    }
+                    log.warn(format!(
+                        "Manifest entries not found in {}: {missing}",
+                        zip_file_path.display()
+                    ));
+                }
+            }
+            if options.latest_only {
+                match superseded_entries(&mut raw, archive.central_directory_start()) {
+                    Ok(superseded) => {
+                        for (name, offset) in superseded {
+                            log.warn(format!(
+                                "Skipping {name} at offset {offset}: superseded by a later entry"
+                            ));
+                            options.skip(SkipReason::Filtered, &name, span);
+                        }
+                    }
+                    Err(e) => log.warn(format!("Fail to read central directory: {e}")),
+                }
+            }
+            let archive_dir = match zip_file_path.file_stem() {
+                Some(stem) if options.keep_root && !has_single_root(archive.file_names()) => {
+                    dir.join(stem)
+                }
+                _ => dir.to_path_buf(),
+            };
+            // create the target directory first to have a place for the resume file
+            let resume_file = if options.resume {
+                std::fs::create_dir_all(&archive_dir).map_err(|error| UnzipError::Create {
+                    path: archive_dir.clone(),
+                    error,
+                    span,
+                })?;
+                Some(archive_dir.join(resume_file_name(zip_file_path)))
+            } else {
+                None
+            };
+            // with --data, there is no archive file to protect
+            let archive_path = options.data.is_none().then_some(zip_file_path.as_path());
+            let run = ArchiveRun::new(&mut archive, &mut raw, options, archive_path, resume_file);
+            let archive_conflicts =
+                self.unzip_file(span, &mut archive, options, &archive_dir, &run, log)?;
+            // an archive held back by conflicts isn't empty
+            if archive_conflicts.is_empty() || options.force {
+                check_empty()?;
+            }
+            conflicts.extend(archive_conflicts);
+            nested.extend(self.unzip_nested(span, options, 0, log)?);
+        }
+        Ok((conflicts, nested))
+    }
+
+    /// Unzip the zips unzipped with `options` since the last call into sibling directories
+    /// named after them, recursing into those down to `--max-depth` levels, and return them
+    fn unzip_nested(
+        &self,
+        span: Span,
+        options: &ExtractOptions,
+        depth: usize,
+        log: &mut Log,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let mut tree = Vec::new();
+        let Some(max_depth) = options.nested_depth else {
+            return Ok(tree);
+        };
+        let nested = options.nested_zips.take();
+        if nested.is_empty() {
+            return Ok(tree);
+        }
+
+        let nested_options = options.nested();
+        for path in nested {
+            if depth >= max_depth {
+                log.warn(format!(
+                    "Not unzipping {}: nested deeper than --max-depth {max_depth}",
+                    path.display()
+                ));
+                continue;
+            }
+            let opened = open_zip_file(&path, span).and_then(|file| {
+                let raw = open_zip_file(&path, span)?;
+                ZipArchive::new(file)
+                    .map(|archive| (archive, raw))
+                    .map_err(|e| UnzipError::Open {
+                        reason: e.to_string(),
+                        span,
+                    })
+            });
+            let (mut nested_archive, mut raw) = match opened {
+                Ok(opened) => opened,
+                Err(e) => {
+                    log.warn(format!(
+                        "Not unzipping {}: {}",
+                        path.display(),
+                        LabeledError::from(e).msg
+                    ));
+                    continue;
+                }
+            };
+            let nested_dir = path.with_extension("");
+            let run = ArchiveRun::new(
+                &mut nested_archive,
+                &mut raw,
+                &nested_options,
+                Some(&path),
+                None,
+            );
+            self.unzip_file(
+                span,
+                &mut nested_archive,
+                &nested_options,
+                &nested_dir,
+                &run,
+                log,
+            )?;
+
+            let mut row = Record::default();
+            row.push("archive", Value::string(path.to_string_lossy(), span));
+            row.push("dir", Value::string(nested_dir.to_string_lossy(), span));
+            row.push("depth", Value::int(depth as i64 + 1, span));
+            tree.push(Value::record(row, span));
+
+            tree.extend(self.unzip_nested(span, &nested_options, depth + 1, log)?);
+        }
+        options.merge_nested(nested_options);
+
+        Ok(tree)
+    }
+
+    /// Body of both `unzip` and `unzip list`, which always lists
+    fn unzip(
+        &self,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+        list: bool,
+    ) -> Result<PipelineData, LabeledError> {
+        let config = PluginConfig::from_value(engine.get_plugin_config()?)?;
+        let current_dir = current_dir(engine.get_current_dir())?;
+
+        let data: Option<Arc<[u8]>> = call.get_flag::<Vec<u8>>("data")?.map(Into::into);
+        let from_dir = call
+            .get_flag::<PathBuf>("from-dir")?
+            .map(|from_dir| current_dir.join(from_dir));
+        let mut zip_file_paths = if let Some(from_dir) = &from_dir {
+            if call.opt::<Value>(0)?.is_some() || data.is_some() {
+                return Err(LabeledError::new("Conflicting arguments").with_label(
+                    "--from-dir gives the archives, no file or --data can be given with it",
+                    call.get_flag_span("from-dir").unwrap_or(call.head),
+                ));
+            }
+            if call.get_flag::<PathBuf>("into")?.is_some() || call.has_flag("events")? {
+                return Err(LabeledError::new("Conflicting flags").with_label(
+                    "--from-dir returns a summary, it can't be used with --into or --events",
+                    call.head,
+                ));
+            }
+            archives_in(from_dir, call.has_flag("recursive")?).map_err(|e| {
+                LabeledError::new(format!("Fail to read {}", from_dir.display()))
+                    .with_label(e.to_string(), call.head)
+            })?
+        } else {
+            // the first argument may be a list, like the output of `glob *.zip`
+            match (call.opt::<Value>(0)?, &data) {
+                (Some(value), Some(_)) => {
+                    return Err(LabeledError::new("Conflicting arguments").with_label(
+                        "--data is the archive, no file can be given with it",
+                        value.span(),
+                    ));
+                }
+                // the data stands in for a file of this name, e.g. for `--keep-root`
+                (None, Some(_)) => vec![PathBuf::from("data")],
+                (None, None) => {
+                    return Err(LabeledError::new("Missing archive")
+                        .with_label("give a file to read, or the archive with --data", call.head));
+                }
+                (Some(value @ Value::List { .. }), None) => Vec::<PathBuf>::from_value(value)?,
+                (Some(value), None) => vec![PathBuf::from_value(value)?],
+            }
+        };
+        zip_file_paths.extend(call.rest::<PathBuf>(1)?);
+
+        let max_size = call
+            .get_flag::<Filesize>("max-size")?
+            .map(|size| size.get().max(0) as u64)
+            .or(config.max_size);
+
+        let max_entries = call.get_flag::<usize>("max-entries")?;
+
+        let offset = call
+            .get_flag::<Filesize>("offset")?
+            .map(|offset| offset.get().max(0) as u64);
+        let open_source = |zip_file_path: &Path| match &data {
+            Some(data) => ArchiveSource::from_data(data, offset, call.head),
+            None => ArchiveSource::open(
+                &current_dir.join(zip_file_path),
+                max_size.unwrap_or(DEFAULT_MAX_BUFFER_SIZE),
+                offset,
+                call.head,
+            ),
+        };
+
+        let mut log = Log {
+            quiet: call.has_flag("quiet")? || config.quiet,
+            debug: call.has_flag("debug")? || config.debug,
+            ..Default::default()
+        };
+
+        if call.has_flag("skip-errors")? && data.is_none() {
+            zip_file_paths.retain(|zip_file_path| {
+                match std::fs::metadata(current_dir.join(zip_file_path)) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        log.warn(format!("Skipping {}: {e}", zip_file_path.display()));
+                        false
+                    }
+                }
+            });
+        }
+
+        let filter = EntryFilter {
+            regex: call
+                .get_flag::<String>("regex")?
+                .map(|pattern| {
+                    parse_regex(&pattern, call.get_flag_span("regex").unwrap_or(call.head))
+                })
+                .transpose()?,
+            min_size: call
+                .get_flag::<Filesize>("min-size")?
+                .map(|size| size.get().max(0) as u64),
+            max_size: call
+                .get_flag::<Filesize>("max-size-entry")?
+                .map(|size| size.get().max(0) as u64),
+            names: match (
+                call.has_flag("entries-from-stdin")?,
+                call.get_flag::<PathBuf>("manifest-in")?,
+            ) {
+                (true, Some(_)) => {
+                    return Err(LabeledError::new("Conflicting flags").with_label(
+                        "--manifest-in and --entries-from-stdin both select entries",
+                        call.head,
+                    ));
+                }
+                (true, None) => Some(normalized_names(Vec::<String>::from_value(
+                    input.into_value(call.head)?,
+                )?)),
+                (false, path) => path
+                    .map(|path| read_manifest(&current_dir.join(path), call.head))
+                    .transpose()?,
+            },
+            extensions: call
+                .get_flag::<Value>("ext")?
+                .map(parse_extensions)
+                .transpose()?,
+            closure: call
+                .get_flag::<Spanned<Closure>>("filter")?
+                .map(|closure| (engine.clone(), closure)),
+            within: call
+                .get_flag::<String>("within")?
+                .map(|within| {
+                    check_prefix(&within, call.get_flag_span("within").unwrap_or(call.head))
+                })
+                .transpose()?,
+            since: call
+                .get_flag::<PathBuf>("since-archive")?
+                .map(|path| {
+                    read_crcs(
+                        &current_dir.join(path),
+                        call.get_flag_span("since-archive").unwrap_or(call.head),
+                    )
+                })
+                .transpose()?,
+        };
+
+        if let Some(entry) = call.get_flag::<String>("entry")? {
+            let range = call.get_flag::<IntRange>("range")?;
+            let format = call.get_flag::<Spanned<String>>("parse")?;
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                if archive.index_for_name(&entry).is_some() {
+                    let data = self.read_range(call.head, &mut archive, &entry, range.as_ref())?;
+                    let value = match &format {
+                        Some(format) => parse_entry(engine, format, data, call.head)?,
+                        None => Value::binary(data, call.head),
+                    };
+                    return Ok(PipelineData::Value(value, None));
+                }
+            }
+            return Err(LabeledError::new(format!("Entry {entry} not found"))
+                .with_label("No archive contains this entry", call.head));
+        }
+
+        if let Some(entry) = call.get_flag::<Spanned<String>>("entry-info")? {
+            let mut names = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                if let Some(i) = archive.index_for_name(&entry.item) {
+                    // raw, as the metadata of encrypted entries needs no password
+                    let file = archive.by_index_raw(i).map_err(|e| {
+                        LabeledError::new(format!("Fail to read {}", entry.item))
+                            .with_label(e.to_string(), entry.span)
+                    })?;
+                    return Ok(PipelineData::Value(entry_info(&file, call.head), None));
+                }
+                names.extend(archive.file_names().map(|name| name.to_string()));
+            }
+            let close = close_names(&entry.item, names.iter().map(|name| name.as_str()));
+            let label = if close.is_empty() {
+                "No archive contains this entry".to_string()
+            } else {
+                format!("Did you mean {}?", close.join(", "))
+            };
+            return Err(LabeledError::new(format!("Entry {} not found", entry.item))
+                .with_label(label, entry.span));
+        }
+
+        if call.has_flag("stats")? {
+            let mut stats = CompressionStats::default();
+            for zip_file_path in &zip_file_paths {
+                let source = open_source(zip_file_path)?;
+                let mut raw = source.try_clone(call.head)?;
+                // an invalid archive is reported in the stats rather than failing
+                let mut archive = match ZipArchive::new(source) {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        let truncated = starts_like_zip(&mut raw);
+                        log.warn(format!(
+                            "{}: {e}{}",
+                            zip_file_path.display(),
+                            if truncated {
+                                ", the archive looks truncated"
+                            } else {
+                                ""
+                            }
+                        ));
+                        stats.invalid = true;
+                        stats.truncated |= truncated;
+                        continue;
+                    }
+                };
+                let len = raw.seek(SeekFrom::End(0)).map_err(|e| UnzipError::Read {
+                    reason: e.to_string(),
+                    span: call.head,
+                })?;
+                if references_beyond(&mut archive, len) {
+                    log.warn(format!(
+                        "{}: entries reference data past the end of the archive, it looks \
+                         truncated",
+                        zip_file_path.display()
+                    ));
+                    stats.invalid = true;
+                    stats.truncated = true;
+                }
+                for i in 0..archive.len() {
+                    let file = match archive.by_index_raw(i) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            log.warn(format!("Skipping entry #{i}: {e}"));
+                            continue;
+                        }
+                    };
+                    if normalized_name(file.name())
+                        .is_some_and(|name| filter.matches(name, &file, &mut log))
+                    {
+                        stats.add(&file);
+                    }
+                }
+            }
+            return Ok(PipelineData::Value(stats.into_value(call.head), None));
+        }
+
+        if call.has_flag("to-json")? {
+            let mut manifests = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                manifests.push(self.manifest(
+                    call.head,
+                    &mut archive,
+                    zip_file_path,
+                    &filter,
+                    &mut log,
+                ));
+            }
+            // one document per archive, a list only when several are given
+            let value = if manifests.len() == 1 {
+                manifests.remove(0)
+            } else {
+                Value::list(manifests, call.head)
+            };
+            return Ok(PipelineData::Value(value, None));
+        }
+
+        let mut tar_output = None;
+        for (flag, compression) in TarCompression::FLAGS {
+            if let Some(path) = call.get_flag::<PathBuf>(flag)? {
+                if tar_output.is_some() {
+                    return Err(LabeledError::new("Conflicting flags").with_label(
+                        "--to-tar, --to-tar-gz and --to-tar-zst are mutually exclusive",
+                        call.head,
+                    ));
+                }
+                tar_output = Some((current_dir.join(path), compression));
+            }
+        }
+        if let Some((tar_path, compression)) = tar_output {
+            let level = compression.level(
+                call.get_flag::<i32>("level")?,
+                call.get_flag_span("level").unwrap_or(call.head),
+            )?;
+            if tar_path.exists() && !(call.has_flag("force")? || config.force) {
+                return Err(UnzipError::Exists {
+                    path: tar_path,
+                    span: call.head,
+                }
+                .into());
+            }
+            let mut archives = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                archives.push(read_archive(open_source(zip_file_path)?, call.head)?);
+            }
+
+            let file = std::fs::File::create(&tar_path).map_err(|error| UnzipError::Create {
+                path: tar_path.clone(),
+                error,
+                span: call.head,
+            })?;
+            let write_error = |error| UnzipError::Write {
+                path: tar_path.clone(),
+                error,
+                span: call.head,
+            };
+            let write = || -> Result<(), LabeledError> {
+                let file = std::io::BufWriter::new(file);
+                let mut file = match compression {
+                    TarCompression::None => {
+                        self.write_tar(call.head, &mut archives, &filter, file, &mut log)?
+                    }
+                    TarCompression::Gzip => {
+                        let encoder = flate2::write::GzEncoder::new(
+                            file,
+                            flate2::Compression::new(level as u32),
+                        );
+                        self.write_tar(call.head, &mut archives, &filter, encoder, &mut log)?
+                            .finish()
+                            .map_err(write_error)?
+                    }
+                    TarCompression::Zstd => {
+                        let encoder = zstd::Encoder::new(file, level).map_err(write_error)?;
+                        self.write_tar(call.head, &mut archives, &filter, encoder, &mut log)?
+                            .finish()
+                            .map_err(write_error)?
+                    }
+                };
+                file.flush().map_err(write_error)?;
+                Ok(())
+            };
+            // like an entry that fails to unzip, don't leave a truncated file behind
+            write().inspect_err(|_| {
+                let _ = std::fs::remove_file(&tar_path);
+            })?;
+            return Ok(PipelineData::Value(Value::nothing(call.head), None));
+        }
+
+        if call.has_flag("extract-to-value")? {
+            let max_size = max_size.unwrap_or(DEFAULT_MAX_BUFFER_SIZE);
+            let mut total_read = 0;
+            let mut record = Record::default();
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                let entries = self.read_entries(
+                    call.head,
+                    &mut archive,
+                    &filter,
+                    max_size,
+                    &mut total_read,
+                    &mut log,
+                )?;
+                // like unzipping to one directory, later archives overwrite earlier ones
+                for (name, value) in entries {
+                    record.insert(name, value);
+                }
+            }
+            return Ok(PipelineData::Value(Value::record(record, call.head), None));
+        }
+
+        if call.has_flag("verify")? {
+            // data has no modification time to tell whether it changed
+            let cache_dir = if !call.has_flag("no-cache")? && data.is_none() {
+                Some(
+                    call.get_flag::<PathBuf>("cache-dir")?
+                        .map_or_else(default_verify_cache_dir, |dir| current_dir.join(dir)),
+                )
+            } else {
+                None
+            };
+            // salvaged entries go where unzipping would put them
+            let repair_dir = if call.has_flag("repair")? {
+                Some(current_dir.join(call.get_flag::<PathBuf>("dir")?.unwrap_or_default()))
+            } else {
+                None
+            };
+            let mut rows = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                let path = current_dir.join(zip_file_path);
+                // an archive that verified and hasn't changed since needs no decompressing
+                let cache = cache_dir
+                    .as_ref()
+                    .and_then(|cache_dir| verify_cache_entry(cache_dir, &path).ok());
+                let cached = cache.as_ref().is_some_and(|(cache_file, key)| {
+                    std::fs::read_to_string(cache_file).is_ok_and(|cached| cached == *key)
+                });
+                let (entries, failed) = if cached {
+                    log.debug(format!("{} verified before, unchanged", path.display()));
+                    (None, Vec::new())
+                } else {
+                    let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                    let (entries, failed) =
+                        self.verify(call.head, &mut archive, repair_dir.as_deref(), &mut log);
+                    (Some(entries), failed)
+                };
+                if let (false, true, Some((cache_file, key))) = (cached, failed.is_empty(), &cache)
+                {
+                    let written = cache_file
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|()| std::fs::write(cache_file, key));
+                    if let Err(e) = written {
+                        log.warn(format!(
+                            "Fail to cache verification of {}: {e}",
+                            path.display()
+                        ));
+                    }
+                }
+
+                let mut row = Record::default();
+                row.push("path", Value::string(path.to_string_lossy(), call.head));
+                row.push("ok", Value::bool(failed.is_empty(), call.head));
+                row.push(
+                    "entries",
+                    entries.map_or(Value::nothing(call.head), |entries| {
+                        Value::int(entries as i64, call.head)
+                    }),
+                );
+                row.push("failed", Value::list(failed, call.head));
+                row.push("cached", Value::bool(cached, call.head));
+                rows.push(Value::record(row, call.head));
+            }
+            return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+        }
+
+        if let Some(list) = call.get_flag::<PathBuf>("verify-list")? {
+            let mut expected = read_crc_list(
+                &current_dir.join(list),
+                call.get_flag_span("verify-list").unwrap_or(call.head),
+            )?;
+            let crc_value = |crc: Option<u32>| {
+                crc.map_or(Value::nothing(call.head), |crc| {
+                    Value::string(format!("{crc:08x}"), call.head)
+                })
+            };
+            let mut rows = Vec::new();
+            let mut push_row = |name: &str, status: &str, expected, actual| {
+                let mut row = Record::default();
+                row.push("name", Value::string(name, call.head));
+                row.push("status", Value::string(status, call.head));
+                row.push("expected", crc_value(expected));
+                row.push("actual", crc_value(actual));
+                rows.push(Value::record(row, call.head));
+            };
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                for i in 0..archive.len() {
+                    // raw, as the CRC is known without decrypting
+                    let file = match archive.by_index_raw(i) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            log.warn(format!("Skipping entry #{i}: {e}"));
+                            continue;
+                        }
+                    };
+                    let Some(name) = normalized_name(file.name()) else {
+                        continue;
+                    };
+                    if !filter.matches(name, &file, &mut log) {
+                        continue;
+                    }
+                    let actual = file.crc32();
+                    match expected.remove(name) {
+                        Some(crc) if crc == actual => push_row(name, "ok", Some(crc), Some(actual)),
+                        Some(crc) => push_row(name, "mismatch", Some(crc), Some(actual)),
+                        None => push_row(name, "extra", None, Some(actual)),
+                    }
+                }
+            }
+            let mut missing: Vec<_> = expected.into_iter().collect();
+            missing.sort();
+            for (name, crc) in missing {
+                push_row(&name, "missing", Some(crc), None);
+            }
+            return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+        }
+
+        if call.has_flag("verify-text")? {
+            let mut rows = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                for i in 0..archive.len() {
+                    let file = match archive.by_index(i) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            log.warn(format!("Skipping entry #{i}: {e}"));
+                            continue;
+                        }
+                    };
+                    let name = match normalized_name(file.name()) {
+                        Some(name) if !file.is_dir() => name.to_string(),
+                        _ => continue,
+                    };
+                    if !filter.matches(&name, &file, &mut log) {
+                        continue;
+                    }
+                    let mut prefix = Vec::with_capacity(TEXT_SNIFF_SIZE);
+                    if let Err(e) = file.take(TEXT_SNIFF_SIZE as u64).read_to_end(&mut prefix) {
+                        log.warn(format!("Skipping {name}: {e}"));
+                        continue;
+                    }
+                    // a CR at the cut may be followed by a LF
+                    if prefix.len() == TEXT_SNIFF_SIZE && prefix.ends_with(b"\r") {
+                        prefix.pop();
+                    }
+                    let mut row = Record::default();
+                    row.push("name", Value::string(name, call.head));
+                    row.push(
+                        "line_ending",
+                        Value::string(line_ending(&prefix), call.head),
+                    );
+                    rows.push(Value::record(row, call.head));
+                }
+            }
+            return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+        }
+
+        let names_only = call.has_flag("names")?;
+        let print0 = call.has_flag("print0")?;
+        let list_only = list || call.has_flag("list")? || names_only || print0;
+        if list_only {
+            let kind = match (call.has_flag("only-files")?, call.has_flag("only-dirs")?) {
+                (true, true) => {
+                    return Err(LabeledError::new("Conflicting flags").with_label(
+                        "--only-files and --only-dirs are mutually exclusive",
+                        call.head,
+                    ))
+                }
+                (true, false) => EntryKind::Files,
+                (false, true) => EntryKind::Dirs,
+                (false, false) => EntryKind::All,
+            };
+            let options = ListOptions {
+                filter,
+                kind,
+                epoch: call.has_flag("epoch")?,
+                crc_hex: call.has_flag("with-crc-hex")?,
+                verbose: call.has_flag("verbose")?,
+                path_style: call
+                    .get_flag::<String>("path-style")?
+                    .map(|style| {
+                        PathStyle::parse(
+                            &style,
+                            call.get_flag_span("path-style").unwrap_or(call.head),
+                        )
+                    })
+                    .transpose()?,
+                // --quick is a shorthand for the DOS time, which needs no extra fields
+                time_source: if call.has_flag("quick")? {
+                    Some(TimeSource::Dos)
+                } else {
+                    call.get_flag::<String>("time-source")?
+                        .map(|source| {
+                            TimeSource::parse(
+                                &source,
+                                call.get_flag_span("time-source").unwrap_or(call.head),
+                            )
+                        })
+                        .transpose()?
+                },
+                preview: call.get_flag::<usize>("preview")?,
+            };
+            let nested = call.has_flag("nested")?;
+            let separator = match call.get_flag::<Spanned<String>>("format")? {
+                None => None,
+                Some(format) => match format.item.as_str() {
+                    "csv" => Some(','),
+                    "tsv" => Some('\t'),
+                    _ => {
+                        return Err(LabeledError::new(format!("Unknown format {}", format.item))
+                            .with_label("expected csv or tsv", format.span))
+                    }
+                },
+            };
+            if nested && separator.is_some() {
+                return Err(LabeledError::new("Conflicting flags")
+                    .with_label("--nested and --format are mutually exclusive", call.head));
+            }
+            if nested && options.path_style.is_some() {
+                return Err(LabeledError::new("Conflicting flags").with_label(
+                    "--nested names entries by their last component, --path-style doesn't apply",
+                    call.head,
+                ));
+            }
+            let with_source = zip_file_paths.len() > 1 || call.has_flag("with-source")?;
+            let local = call.has_flag("local")?;
+            if local && options.verbose {
+                return Err(LabeledError::new("Conflicting flags").with_label(
+                    "--verbose compares with the central directory, which --local doesn't read",
+                    call.head,
+                ));
+            }
+
+            let fail_if_empty = call.has_flag("fail-if-empty")?;
+
+            let mut rows = Vec::new();
+            for zip_file_path in &zip_file_paths {
+                let source = zip_file_path.to_string_lossy();
+                let archive_rows = if local {
+                    // local headers are read in order, so no need to buffer non-seekable files
+                    let zip_file: Box<dyn Read> = match (offset, &data) {
+                        (None, None) => {
+                            Box::new(open_zip_file(&current_dir.join(zip_file_path), call.head)?)
+                        }
+                        _ => Box::new(open_source(zip_file_path)?),
+                    };
+                    self.list_local(
+                        call.head,
+                        &mut std::io::BufReader::new(zip_file),
+                        &options,
+                        with_source.then_some(source.as_ref()),
+                        &mut log,
+                    )?
+                } else {
+                    let zip_file = open_source(zip_file_path)?;
+                    let mut raw = zip_file.try_clone(call.head)?;
+                    let mut archive = read_archive(zip_file, call.head)?;
+                    check_entry_count(archive.len(), max_entries, call.head)?;
+                    self.list_files(
+                        call.head,
+                        &mut archive,
+                        &mut raw,
+                        &options,
+                        with_source.then_some(source.as_ref()),
+                        &mut log,
+                    )?
+                };
+                if fail_if_empty && archive_rows.is_empty() {
+                    return Err(no_entries_error("list", zip_file_path, call.head));
+                }
+                rows.extend(archive_rows);
+            }
+
+            let dirs_first = call.has_flag("sort-dirs-first")?;
+            if call.has_flag("sorted")? || dirs_first {
+                // directories are stored with a trailing separator, whatever `--path-style`
+                let key = |row: &Value| -> (bool, String) {
+                    let name = row
+                        .as_record()
+                        .ok()
+                        .and_then(|row| row.get("name")?.as_str().ok())
+                        .unwrap_or_default();
+                    (dirs_first && !name.ends_with(['/', '\\']), name.to_string())
+                };
+                rows.sort_by_cached_key(key);
+            }
+
+            if names_only || print0 {
+                let names = rows
+                    .iter()
+                    .filter_map(|row| row.as_record().ok()?.get("name")?.as_str().ok())
+                    .map(|name| name.to_string());
+                let value = if print0 {
+                    Value::string(names.collect::<Vec<_>>().join("\0"), call.head)
+                } else {
+                    Value::list(
+                        names.map(|name| Value::string(name, call.head)).collect(),
+                        call.head,
+                    )
+                };
+                return Ok(PipelineData::Value(value, None));
+            }
+            if let Some(separator) = separator {
+                let text = delimited(&rows, separator);
+                return Ok(PipelineData::Value(Value::string(text, call.head), None));
+            }
+            if nested {
+                let mut tree = ManifestDir::default();
+                for row in rows {
+                    let Ok(mut row) = row.into_record() else {
+                        continue;
+                    };
+                    let Some(name) = row.get("name").and_then(|name| name.as_str().ok()) else {
+                        continue;
+                    };
+                    let name = name.to_string();
+                    if name.ends_with('/') {
+                        tree.insert(&name, None);
+                        continue;
+                    }
+                    // the position in the tree replaces the full name and depth
+                    let base_name = name.rsplit('/').next().unwrap_or(&name);
+                    let mut leaf = Record::default();
+                    leaf.push("name", Value::string(base_name, call.head));
+                    leaf.push("type", Value::string("file", call.head));
+                    row.remove("name");
+                    row.remove("depth");
+                    leaf.extend(row);
+                    tree.insert(&name, Some(Value::record(leaf, call.head)));
+                }
+                return Ok(PipelineData::Value(tree.into_children(call.head), None));
+            }
+            Ok(PipelineData::Value(Value::list(rows, call.head), None))
+        } else {
+            let options = ExtractOptions {
+                filter,
+                force: call.has_flag("force")? || config.force,
+                strip_components: call.get_flag::<usize>("strip-components")?.unwrap_or(0),
+                strip_trailing: call.get_flag::<usize>("strip-trailing")?.unwrap_or(0),
+                add_prefix: call
+                    .get_flag::<String>("add-prefix")?
+                    .map(|prefix| check_prefix(&prefix, call.head))
+                    .transpose()?,
+                out_template: call
+                    .get_flag::<String>("out-template")?
+                    .map(|template| {
+                        OutTemplate::parse(
+                            &template,
+                            call.get_flag_span("out-template").unwrap_or(call.head),
+                        )
+                    })
+                    .transpose()?,
+                max_size,
+                report_conflicts: call.has_flag("report-conflicts")?,
+                strict: call.has_flag("strict")?,
+                confine: call.has_flag("confine")?,
+                transform: call
+                    .get_flag::<Spanned<Closure>>("path-transform")?
+                    .map(|closure| (engine.clone(), closure)),
+                fsync: call.has_flag("fsync")?,
+                strip_bom: call.has_flag("strip-bom")?,
+                offset,
+                data: data.clone(),
+                // a format alone is enough to ask for renaming
+                rename: match call.get_flag::<String>("conflict-format")? {
+                    Some(format) => Some(ConflictFormat::parse(
+                        &format,
+                        call.get_flag_span("conflict-format").unwrap_or(call.head),
+                    )?),
+                    None => call
+                        .has_flag("rename-on-conflict")?
+                        .then(|| ConflictFormat::parse(ConflictFormat::DEFAULT, call.head))
+                        .transpose()?,
+                },
+                out_encoding: match call.get_flag::<Spanned<String>>("out-encoding")? {
+                    Some(label) => Some(OutEncoding::parse(
+                        &label,
+                        call.get_flag::<Spanned<String>>("unmappable")?,
+                    )?),
+                    None => None,
+                },
+                skip_hidden: call.has_flag("skip-hidden")?,
+                allow_reparse: call.has_flag("allow-reparse")?,
+                sizes: call
+                    .has_flag("report-by-size")?
+                    .then(|| RefCell::new(Vec::new())),
+                fail_if_empty: call.has_flag("fail-if-empty")?,
+                dedupe: call.has_flag("dedupe-by-crc")?,
+                atomic: call.has_flag("atomic")?,
+                max_entries,
+                nested_depth: if call.has_flag("recursive-zips")? {
+                    Some(
+                        call.get_flag::<usize>("max-depth")?
+                            .unwrap_or(DEFAULT_NESTED_DEPTH),
+                    )
+                } else {
+                    None
+                },
+                nested_zips: RefCell::default(),
+                keep_root: call.has_flag("keep-root")?,
+                resume: call.has_flag("resume")?,
+                latest_only: call.has_flag("latest-only")?,
+                skip_errors: call.has_flag("skip-errors")?,
+                ownership: if call.has_flag("preserve-ownership")? {
+                    Some(
+                        call.get_flag::<Value>("owner-map")?
+                            .map(|rules| {
+                                parse_owner_rules(
+                                    rules,
+                                    call.get_flag_span("owner-map").unwrap_or(call.head),
+                                )
+                            })
+                            .transpose()?
+                            .unwrap_or_default(),
+                    )
+                } else {
+                    None
+                },
+                events: None,
+                report: RefCell::default(),
+                file_mode: call
+                    .get_flag::<String>("chmod")?
+                    .map(|mode| parse_mode(&mode, call.get_flag_span("chmod").unwrap_or(call.head)))
+                    .transpose()?,
+                dir_mode: call
+                    .get_flag::<String>("chmod-dirs")?
+                    .map(|mode| {
+                        parse_mode(&mode, call.get_flag_span("chmod-dirs").unwrap_or(call.head))
+                    })
+                    .transpose()?,
+                deadline: call
+                    .get_flag::<i64>("timeout")?
+                    .map(|nanos| Instant::now() + Duration::from_nanos(nanos.max(0) as u64)),
+                passwords: if let Some(value) = call.get_flag::<Value>("password")? {
+                    parse_passwords(value)?
+                } else if let Some(path) = call.get_flag::<PathBuf>("password-file")? {
+                    read_password_file(&current_dir.join(path), call.head)?
+                } else if let Some(value) = engine.get_env_var(PASSWORD_ENV_VAR)? {
+                    vec![String::from_value(value)?]
+                } else {
+                    Vec::new()
+                },
+            };
+
+            if let Some(compare_dir) = call.get_flag::<PathBuf>("compare-to")? {
+                let compare_dir = current_dir.join(compare_dir);
+                let mut seen = HashSet::new();
+                let mut rows = Vec::new();
+                for zip_file_path in &zip_file_paths {
+                    let mut archive = read_archive(open_source(zip_file_path)?, call.head)?;
+                    rows.extend(self.compare_entries(
+                        call.head,
+                        &mut archive,
+                        &options,
+                        &compare_dir,
+                        &mut seen,
+                        &mut log,
+                    )?);
+                }
+
+                let mut on_disk = Vec::new();
+                walk_files(&compare_dir, &mut on_disk).map_err(|e| {
+                    LabeledError::new(format!("Fail to read {}", compare_dir.display()))
+                        .with_label(e.to_string(), call.head)
+                })?;
+                on_disk.sort();
+                for path in on_disk.into_iter().filter(|path| !seen.contains(path)) {
+                    let name = path.strip_prefix(&compare_dir).unwrap_or(&path);
+                    let mut row = Record::default();
+                    row.push("name", Value::string(name.to_string_lossy(), call.head));
+                    row.push("path", Value::string(path.to_string_lossy(), call.head));
+                    row.push("status", Value::string("removed", call.head));
+                    rows.push(Value::record(row, call.head));
+                }
+                return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+            }
+
+            if cfg!(not(unix)) && (options.file_mode.is_some() || options.dir_mode.is_some()) {
+                log.warn("--chmod and --chmod-dirs are only supported on unix, ignoring them");
+            }
+            if cfg!(not(unix)) && options.ownership.is_some() {
+                log.warn("--preserve-ownership is only supported on unix, ignoring it");
+            }
+            if options.ownership.is_none() && call.get_flag::<Value>("owner-map")?.is_some() {
+                log.warn("--owner-map only applies with --preserve-ownership, ignoring it");
+            }
+
+            // joining an absolute path replaces the current directory
+            let dir_flag = call.get_flag::<PathBuf>("dir")?;
+            let spool = call.has_flag("spool")?;
+            let into = call
+                .get_flag::<PathBuf>("into")?
+                .map(|into| current_dir.join(into));
+            let tmp = call.has_flag("tmp")?;
+            let dir = if spool {
+                if dir_flag.is_some() || into.is_some() || tmp {
+                    return Err(LabeledError::new("Conflicting flags").with_label(
+                        "--spool, --dir, --into and --tmp are mutually exclusive",
+                        call.head,
+                    ));
+                }
+                // the spool lives as long as the plugin, so it isn't stopped while idle
+                engine.set_gc_disabled(true)?;
+                create_spool_dir(call.head)?
+            } else if let Some(into) = &into {
+                if dir_flag.is_some() || tmp || call.has_flag("events")? {
+                    return Err(LabeledError::new("Conflicting flags").with_label(
+                        "--into can't be used with --dir, --tmp or --events",
+                        call.head,
+                    ));
+                }
+                if !into.is_dir() {
+                    return Err(
+                        LabeledError::new(format!("{} isn't a directory", into.display()))
+                            .with_label("--into merges into an existing directory", call.head),
+                    );
+                }
+                let staging = staging_dir(into);
+                std::fs::create_dir_all(&staging).map_err(|error| UnzipError::Create {
+                    path: staging.clone(),
+                    error,
+                    span: call.head,
+                })?;
+                staging
+            } else if tmp {
+                if dir_flag.is_some() {
+                    return Err(LabeledError::new("Conflicting flags")
+                        .with_label("--tmp and --dir are mutually exclusive", call.head));
+                }
+                create_tmp_dir(call.head)?
+            } else {
+                match dir_flag.or(config.dir) {
+                    Some(dir) => current_dir.join(dir),
+                    None => current_dir.clone(),
+                }
+            };
+
+            let zip_file_paths: Vec<_> = zip_file_paths
+                .iter()
+                .map(|zip_file_path| current_dir.join(zip_file_path))
+                .collect();
+            let max_buffer_size = max_size.unwrap_or(DEFAULT_MAX_BUFFER_SIZE);
+            if call.has_flag("delete-archive")? && (data.is_some() || call.has_flag("events")?) {
+                return Err(LabeledError::new("Conflicting flags").with_label(
+                    "--delete-archive needs a file, and to know unzipping succeeded without \
+                     --events",
+                    call.head,
+                ));
+            }
+            if call.has_flag("delete-archive")? && from_dir.is_some() {
+                return Err(LabeledError::new("Conflicting flags").with_label(
+                    "--delete-archive isn't supported with --from-dir",
+                    call.head,
+                ));
+            }
+            if call.has_flag("events")? {
+                let (sender, receiver) = std::sync::mpsc::channel();
+                let errors = sender.clone();
+                let options = ExtractOptions {
+                    events: Some(sender),
+                    ..options
+                };
+                let span = call.head;
+                std::thread::spawn(move || {
+                    let result = UnzipCommand.unzip_archives(
+                        span,
+                        &zip_file_paths,
+                        &options,
+                        &dir,
+                        max_buffer_size,
+                        &mut log,
+                    );
+                    if let Err(e) = result {
+                        let _ = errors.send(Value::error(e.into(), span));
+                    }
+                });
+                let stream = ListStream::new(receiver.into_iter(), span, engine.signals().clone());
+                return Ok(PipelineData::ListStream(stream, None));
+            }
+
+            if let Some(from_dir) = &from_dir {
+                let skip_errors = options.skip_errors;
+                let mut rows = Vec::new();
+                for zip_file_path in &zip_file_paths {
+                    // each archive goes into a directory named after it, like downloads/a for
+                    // downloads/a.zip
+                    let relative = zip_file_path
+                        .strip_prefix(from_dir)
+                        .unwrap_or(zip_file_path);
+                    let archive_dir = dir.join(relative.with_extension(""));
+                    let extracted = options.report.borrow().extracted;
+                    let result = self.unzip_archives(
+                        call.head,
+                        std::slice::from_ref(zip_file_path),
+                        &options,
+                        &archive_dir,
+                        max_buffer_size,
+                        &mut log,
+                    );
+                    let errors = match result {
+                        Ok(_) => Value::nothing(call.head),
+                        Err(e) if skip_errors => {
+                            log.warn(format!("Skipping {}: {}", zip_file_path.display(), e.msg));
+                            Value::string(e.msg, call.head)
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    let mut row = Record::default();
+                    row.push(
+                        "archive",
+                        Value::string(zip_file_path.to_string_lossy(), call.head),
+                    );
+                    row.push(
+                        "extracted",
+                        Value::int(
+                            (options.report.borrow().extracted - extracted) as i64,
+                            call.head,
+                        ),
+                    );
+                    row.push("errors", errors);
+                    rows.push(Value::record(row, call.head));
+                }
+                return Ok(PipelineData::Value(Value::list(rows, call.head), None));
+            }
+
+            let unzipped = self.unzip_archives(
+                call.head,
+                &zip_file_paths,
+                &options,
+                &dir,
+                max_buffer_size,
+                &mut log,
+            );
+            let (conflicts, nested) = match &into {
+                // the target is only touched once everything is unzipped
+                Some(into) => {
+                    let merged = unzipped.and_then(|unzipped| {
+                        merge_into(&dir, into, options.force, call.head).map(|()| unzipped)
+                    });
+                    let _ = std::fs::remove_dir_all(&dir);
+                    merged?
+                }
+                None => unzipped?,
+            };
+            if call.has_flag("delete-archive")? {
+                // archives held back by conflicts or missing entries are kept
+                if !options.report.borrow().complete() || (!conflicts.is_empty() && !options.force)
+                {
+                    log.warn("Keeping the archives, not all entries were unzipped");
+                } else {
+                    for zip_file_path in &zip_file_paths {
+                        std::fs::remove_file(zip_file_path).map_err(|e| {
+                            LabeledError::new(format!("Fail to delete {}", zip_file_path.display()))
+                                .with_label(e.to_string(), call.head)
+                        })?;
+                    }
+                }
+            }
+            if spool {
+                let mut files = Vec::new();
+                walk_files(&dir, &mut files).map_err(|e| {
+                    LabeledError::new(format!("Fail to read {}", dir.display()))
+                        .with_label(e.to_string(), call.head)
+                })?;
+                files.sort();
+                let mut record = Record::default();
+                record.push("dir", Value::string(dir.to_string_lossy(), call.head));
+                record.push(
+                    "files",
+                    Value::list(
+                        files
+                            .iter()
+                            .map(|file| Value::string(file.to_string_lossy(), call.head))
+                            .collect(),
+                        call.head,
+                    ),
+                );
+                Ok(PipelineData::Value(Value::record(record, call.head), None))
+            } else if tmp {
+                Ok(PipelineData::Value(
+                    Value::string(dir.to_string_lossy(), call.head),
+                    None,
+                ))
+            } else if let Some(sizes) = options.sizes {
+                let mut sizes = sizes.into_inner();
+                // largest first, ties by path for a stable report
+                sizes.sort_by(|(a_path, a_size), (b_path, b_size)| {
+                    b_size.cmp(a_size).then_with(|| a_path.cmp(b_path))
+                });
+                let rows = sizes
+                    .into_iter()
+                    .map(|(path, size)| {
+                        let mut row = Record::default();
+                        row.push("path", Value::string(path.to_string_lossy(), call.head));
+                        row.push("size", Value::filesize(size as i64, call.head));
+                        Value::record(row, call.head)
+                    })
+                    .collect();
+                Ok(PipelineData::Value(Value::list(rows, call.head), None))
+            } else if options.report_conflicts {
+                Ok(PipelineData::Value(Value::list(conflicts, call.head), None))
+            } else if options.nested_depth.is_some() {
+                Ok(PipelineData::Value(Value::list(nested, call.head), None))
+            } else if call.has_flag("report")? {
+                let report = options.report.into_inner();
+                Ok(PipelineData::Value(report.into_value(call.head), None))
+            } else if options.confine {
+                let refused = options.report.into_inner().count(SkipReason::Confined);
+                Ok(PipelineData::Value(
+                    Value::int(refused as i64, call.head),
+                    None,
+                ))
+            } else {
+                Ok(PipelineData::Value(Value::nothing(call.head), None))
+            }
+        }
+    }
+}
+
+impl PluginCommand for UnzipCommand {
+    type Plugin = UnzipPlugin;
+
+    fn name(&self) -> &str {
+        "unzip"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("unzip")
+            .switch(
+                "list",
+                "list files in zip file, return table<name, size, modified, depth>",
+                Some('l'),
+            )
+            .switch("only-files", "with --list, only list file entries", None)
+            .switch(
+                "only-dirs",
+                "with --list, only list directory entries",
+                None,
+            )
+            .switch(
+                "epoch",
+                "with --list, add a modified_epoch column in Unix seconds",
+                None,
+            )
+            .switch(
+                "with-crc-hex",
+                "with --list, add a crc32 column in hex, as --verify-list reads it",
+                None,
+            )
+            .named(
+                "verify-list",
+                SyntaxShape::Filepath,
+                "check entries against a name,crc32 listing saved from --with-crc-hex",
+                None,
+            )
+            .switch("force", "force overwrite", Some('f'))
+            .switch(
+                "report-conflicts",
+                "report entries that would overwrite files, unzip only if none or --force",
+                None,
+            )
+            .switch(
+                "fsync",
+                "make each unzipped file durable on disk before the next, slower but crash safe",
+                None,
+            )
+            .switch(
+                "strip-bom",
+                "drop the UTF-8 or UTF-16 byte order mark of text entries, binaries are kept",
+                None,
+            )
+            .switch(
+                "report",
+                "return how many entries were unzipped and how many were skipped, by reason",
+                None,
+            )
+            .switch(
+                "dedupe-by-crc",
+                "write files with the same CRC32 and size once, hard linking (or copying) the rest",
+                None,
+            )
+            .switch(
+                "keep-root",
+                "unzip into a directory named after the archive, unless all entries share one",
+                None,
+            )
+            .switch(
+                "recursive-zips",
+                "unzip zips among the unzipped files into directories named after them",
+                None,
+            )
+            .named(
+                "max-depth",
+                SyntaxShape::Int,
+                "with --recursive-zips, how many levels of nested zips to unzip (default 5)",
+                None,
+            )
+            .switch(
+                "events",
+                "stream a record for each step of unzipping, event is start, done, skip or error",
+                None,
+            )
+            .switch(
+                "spool",
+                "unzip to a temporary directory kept until unzip clean, returns {dir, files}",
+                None,
+            )
+            .switch(
+                "latest-only",
+                "report entries superseded by a later one with the same name, which are never unzipped",
+                None,
+            )
+            .switch(
+                "resume",
+                "skip entries an interrupted run already unzipped, tracked in a hidden file",
+                None,
+            )
+            .switch(
+                "atomic",
+                "write each file to a temporary name first, renaming it when complete",
+                None,
+            )
+            .switch(
+                "strict",
+                "refuse to unzip archives with absolute entry paths instead of skipping them",
+                None,
+            )
+            .named(
+                "into",
+                SyntaxShape::Directory,
+                "unzip to a staging directory, then merge into this existing one if all went well",
+                None,
+            )
+            .switch(
+                "report-by-size",
+                "return the unzipped files with their size, largest first",
+                None,
+            )
+            .switch(
+                "tmp",
+                "unzip to a new directory in /dev/shm on Linux, else the temp dir, returning it",
+                None,
+            )
+            .switch(
+                "delete-archive",
+                "delete the archives once all their entries are unzipped",
+                None,
+            )
+            .switch(
+                "allow-reparse",
+                "on Windows, create reparse point entries as links instead of plain files",
+                None,
+            )
+            .switch(
+                "skip-hidden",
+                "skip entries with the hidden or system attribute of Windows archives",
+                None,
+            )
+            .switch(
+                "fail-if-empty",
+                "fail for archives nothing is listed or unzipped from, e.g. after filtering",
+                None,
+            )
+            .switch(
+                "confine",
+                "refuse entries resolving outside the target directory via symlinks, count them",
+                None,
+            )
+            .switch(
+                "skip-errors",
+                "warn about missing archives or entries and skip corrupted entries, not failing",
+                None,
+            )
+            .switch("debug", "print debug information", None)
+            .switch("quiet", "do not print warnings", Some('q'))
+            .named(
+                "dir",
+                SyntaxShape::Directory,
+                "the directory to unzip to, default current directory",
+                Some('d'),
+            )
+            .named(
+                "regex",
+                SyntaxShape::String,
+                "only list or unzip entries whose name matches this regular expression",
+                None,
+            )
+            .named(
+                "strip-components",
+                SyntaxShape::Int,
+                "drop this many leading path components from entries when unzipping",
+                None,
+            )
+            .named(
+                "strip-trailing",
+                SyntaxShape::Int,
+                "drop this many directories above each entry when unzipping, keeping file names",
+                None,
+            )
+            .named(
+                "within",
+                SyntaxShape::String,
+                "only unzip entries below this directory, moving them up to the target directory",
+                None,
+            )
+            .named(
+                "path-transform",
+                SyntaxShape::Closure(Some(vec![SyntaxShape::String])),
+                "closure turning each entry name into the relative path to unzip it to, or null",
+                None,
+            )
+            .named(
+                "add-prefix",
+                SyntaxShape::String,
+                "prepend a relative path to entries when unzipping, after --strip-components",
+                None,
+            )
+            .named(
+                "compare-to",
+                SyntaxShape::Directory,
+                "compare entries to the files in a directory instead of unzipping",
+                None,
+            )
+            .named(
+                "out-template",
+                SyntaxShape::String,
+                "output path of each entry, from {dir} {path} {name} {year} {month} {day}",
+                None,
+            )
+            .switch(
+                "rename-on-conflict",
+                "give files that would overwrite existing ones a numbered name, like a (1).txt",
+                None,
+            )
+            .named(
+                "conflict-format",
+                SyntaxShape::String,
+                "with --rename-on-conflict, the renamed files from {stem} {n} {ext}",
+                None,
+            )
+            .named(
+                "out-encoding",
+                SyntaxShape::String,
+                "write the names of unzipped files in this encoding, like shift_jis, on unix",
+                None,
+            )
+            .named(
+                "unmappable",
+                SyntaxShape::String,
+                "with --out-encoding, skip, replace or error on names it can't write",
+                None,
+            )
+            .named(
+                "password",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::String)),
+                    SyntaxShape::String,
+                ]),
+                "password of encrypted entries, or a list of passwords to try in order",
+                Some('p'),
+            )
+            .named(
+                "manifest-in",
+                SyntaxShape::Filepath,
+                "only list or unzip the entries named in this file, a NUON list or one per line",
+                None,
+            )
+            .named(
+                "since-archive",
+                SyntaxShape::Filepath,
+                "only list or unzip entries added or changed since this archive, by name and CRC",
+                None,
+            )
+            .switch(
+                "entries-from-stdin",
+                "only unzip the entries named in the piped list, like --manifest-in",
+                None,
+            )
+            .named(
+                "password-file",
+                SyntaxShape::Filepath,
+                "file with passwords to try in order, one per line",
+                None,
+            )
+            .named(
+                "chmod",
+                SyntaxShape::String,
+                "octal mode for all unzipped files, like 0644 (unix only)",
+                None,
+            )
+            .switch(
+                "preserve-ownership",
+                "give unzipped paths the uid and gid stored in the archive (unix only)",
+                None,
+            )
+            .named(
+                "owner-map",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::String)),
+                    SyntaxShape::String,
+                ]),
+                "with --preserve-ownership, rules like 1000:1000=0:0 translating stored owners",
+                None,
+            )
+            .named(
+                "chmod-dirs",
+                SyntaxShape::String,
+                "octal mode for all unzipped directories, like 0755 (unix only)",
+                None,
+            )
+            .named(
+                "max-entries",
+                SyntaxShape::Int,
+                "refuse archives with more entries than this",
+                None,
+            )
+            .named(
+                "offset",
+                SyntaxShape::Filesize,
+                "read the archive from this offset in the file, for archives embedded in others",
+                None,
+            )
+            .named(
+                "data",
+                SyntaxShape::Binary,
+                "the archive itself as binary, e.g. from a variable, instead of a file",
+                None,
+            )
+            .named(
+                "timeout",
+                SyntaxShape::Duration,
+                "abort unzipping if it takes longer than this",
+                None,
+            )
+            .named(
+                "min-size",
+                SyntaxShape::Filesize,
+                "only list or unzip entries at least this large",
+                None,
+            )
+            .named(
+                "max-size-entry",
+                SyntaxShape::Filesize,
+                "only list or unzip entries at most this large, unlike --max-size for each entry",
+                None,
+            )
+            .named(
+                "ext",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::String)),
+                    SyntaxShape::String,
+                ]),
+                "only list or unzip files with these extensions, like md,txt or [md txt]",
+                None,
+            )
+            .named(
+                "filter",
+                SyntaxShape::Closure(Some(vec![SyntaxShape::Record(vec![])])),
+                "only list or unzip entries this closure returns true for, given each entry",
+                None,
+            )
+            .named(
+                "max-size",
+                SyntaxShape::Filesize,
+                "abort if more data would be unzipped, or buffered for non-seekable files",
+                None,
+            )
+            .switch(
+                "verbose",
+                "with --list, add header details, encryption, compression, names and extra fields",
+                Some('v'),
+            )
+            .named(
+                "entry",
+                SyntaxShape::String,
+                "return the contents of this entry instead of unzipping",
+                None,
+            )
+            .named(
+                "entry-info",
+                SyntaxShape::String,
+                "return the metadata of this entry instead of unzipping",
+                None,
+            )
+            .named(
+                "parse",
+                SyntaxShape::String,
+                "with --entry, parse the contents as csv, json, toml, yaml or nuon",
+                None,
+            )
+            .named(
+                "range",
+                SyntaxShape::Range,
+                "with --entry, return only this byte range of the entry",
+                None,
+            )
+            .switch(
+                "with-source",
+                "with --list, add a source column naming the archive of each entry",
+                None,
+            )
+            .switch(
+                "sorted",
+                "with --list, sort entries by name instead of keeping the archive order",
+                None,
+            )
+            .switch(
+                "sort-dirs-first",
+                "with --list, sort directories before files, each by name",
+                None,
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "with --list, return csv or tsv text instead of a table, sizes in bytes",
+                None,
+            )
+            .switch(
+                "nested",
+                "with --list, nest entries in directory records with children lists",
+                None,
+            )
+            .switch("names", "list only the names of the entries", None)
+            .switch(
+                "quick",
+                "with --list, take modification times from the DOS time only, 2s precision",
+                None,
+            )
+            .named(
+                "time-source",
+                SyntaxShape::String,
+                "with --list, take modification times from extended, ntfs, dos or newest",
+                None,
+            )
+            .switch(
+                "local",
+                "with --list, read local headers from start to end, not the central directory",
+                None,
+            )
+            .named(
+                "preview",
+                SyntaxShape::Int,
+                "with --list, add a preview column with this many leading bytes of each file",
+                None,
+            )
+            .named(
+                "path-style",
+                SyntaxShape::String,
+                "with --list, separate names with native, unix or windows separators",
+                None,
+            )
+            .named(
+                "to-tar",
+                SyntaxShape::Filepath,
+                "write the entries to this tar file instead of unzipping",
+                None,
+            )
+            .named(
+                "to-tar-gz",
+                SyntaxShape::Filepath,
+                "write the entries to this gzip compressed tar file instead of unzipping",
+                None,
+            )
+            .named(
+                "to-tar-zst",
+                SyntaxShape::Filepath,
+                "write the entries to this zstd compressed tar file instead of unzipping",
+                None,
+            )
+            .named(
+                "level",
+                SyntaxShape::Int,
+                "level of --to-tar-gz (0-9, default 6) or --to-tar-zst (1-22, default 3)",
+                None,
+            )
+            .switch(
+                "extract-to-value",
+                "return a record of the file contents keyed by path instead of writing them",
+                None,
+            )
+            .switch(
+                "verify",
+                "decompress every entry to check its CRC32, remembering unchanged archives",
+                None,
+            )
+            .switch(
+                "no-cache",
+                "with --verify, check archives again even if they verified before",
+                None,
+            )
+            .switch(
+                "repair",
+                "with --verify, save what decompresses of damaged entries to <name>.partial",
+                None,
+            )
+            .named(
+                "cache-dir",
+                SyntaxShape::Filepath,
+                "with --verify, where to remember verified archives, a temporary one by default",
+                None,
+            )
+            .switch(
+                "verify-text",
+                "report the line endings of each entry, sniffed from its first 8 KiB",
+                None,
+            )
+            .switch(
+                "to-json",
+                "describe the archive as one record with entries nested by directory",
+                None,
+            )
+            .switch(
+                "stats",
+                "summarize compression methods and ratios of all entries",
+                None,
+            )
+            .switch(
+                "print0",
+                "list only the names of the entries, as one NUL separated string",
+                None,
+            )
+            .optional(
+                "file",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::Filepath)),
+                    SyntaxShape::Filepath,
+                ]),
+                "the file to unzip, or a list of files",
+            )
+            .rest("files", SyntaxShape::Filepath, "more files to unzip")
+            .named(
+                "from-dir",
+                SyntaxShape::Directory,
+                "unzip every .zip in this directory, each into a directory named after it",
+                None,
+            )
+            .switch(
+                "recursive",
+                "with --from-dir, also unzip the archives in its subdirectories",
+                None,
+            )
+            .input_output_types(vec![
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("name".into(), Type::String),
+                        ("size".into(), Type::Filesize),
+                        ("modified".into(), Type::Date),
+                        ("depth".into(), Type::Int),
+                    ])),
+                ),
+                (Type::Nothing, Type::List(Box::new(Type::String))),
+                (Type::Nothing, Type::String),
+                (Type::Nothing, Type::Binary),
+                (Type::Nothing, Type::Record(Box::new([]))),
+                (Type::Nothing, Type::Nothing),
+                (Type::List(Box::new(Type::String)), Type::Nothing),
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("name".into(), Type::String),
+                        ("path".into(), Type::String),
+                        ("size".into(), Type::Filesize),
+                        ("existing_size".into(), Type::Filesize),
+                        ("differs".into(), Type::Bool),
+                    ])),
+                ),
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("name".into(), Type::String),
+                        ("path".into(), Type::String),
+                        ("status".into(), Type::String),
+                    ])),
+                ),
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("name".into(), Type::String),
+                        ("line_ending".into(), Type::String),
+                    ])),
+                ),
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("event".into(), Type::String),
+                        ("name".into(), Type::String),
+                        ("bytes".into(), Type::Filesize),
+                    ])),
+                ),
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("archive".into(), Type::String),
+                        ("dir".into(), Type::String),
+                        ("depth".into(), Type::Int),
+                    ])),
+                ),
+            ])
+            .allow_variants_without_examples(true)
+            .category(Category::FileSystem)
+            .filter()
+    }
+
+    fn description(&self) -> &str {
+        "unzip file"
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        self.unzip(engine, call, input, false)
+    }
+}
+
+impl PluginCommand for UnzipListCommand {
+    type Plugin = UnzipPlugin;
+
+    fn name(&self) -> &str {
+        "unzip list"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("unzip list")
+            .switch("only-files", "only list file entries", None)
+            .switch("only-dirs", "only list directory entries", None)
+            .switch("epoch", "add a modified_epoch column in Unix seconds", None)
+            .switch(
+                "with-crc-hex",
+                "add a crc32 column in hex, as unzip --verify-list reads it",
+                None,
+            )
+            .switch(
+                "verbose",
+                "add header details, encryption, compression, names and extra fields",
+                Some('v'),
+            )
+            .switch(
+                "with-source",
+                "add a source column naming the archive of each entry",
+                None,
+            )
+            .switch(
+                "fail-if-empty",
+                "fail for archives nothing is listed from, e.g. after filtering",
+                None,
+            )
+            .switch(
+                "sorted",
+                "sort entries by name instead of keeping the archive order",
+                None,
+            )
+            .switch(
+                "sort-dirs-first",
+                "sort directories before files, each by name",
+                None,
+            )
+            .named(
+                "format",
+                SyntaxShape::String,
+                "return csv or tsv text instead of a table, sizes in bytes",
+                None,
+            )
+            .switch(
+                "nested",
+                "nest entries in directory records with children lists",
+                None,
+            )
+            .switch("names", "list only the names of the entries", None)
+            .switch(
+                "print0",
+                "list only the names of the entries, as one NUL separated string",
+                None,
+            )
+            .switch(
+                "quick",
+                "take modification times from the DOS time only, 2s precision",
+                None,
+            )
+            .named(
+                "time-source",
+                SyntaxShape::String,
+                "take modification times from extended, ntfs, dos or newest",
+                None,
+            )
+            .switch(
+                "local",
+                "read local headers from start to end, slower, not the central directory",
+                None,
+            )
+            .named(
+                "preview",
+                SyntaxShape::Int,
+                "add a preview column with this many leading bytes of each file, slower",
+                None,
+            )
+            .switch(
+                "skip-errors",
+                "warn about archives that don't exist instead of failing",
+                None,
+            )
+            .switch("debug", "print debug information", None)
+            .switch("quiet", "do not print warnings", Some('q'))
+            .named(
+                "path-style",
+                SyntaxShape::String,
+                "separate names with native, unix or windows separators",
+                None,
+            )
+            .named(
+                "regex",
+                SyntaxShape::String,
+                "only list entries whose name matches this regular expression",
+                None,
+            )
+            .named(
+                "manifest-in",
+                SyntaxShape::Filepath,
+                "only list the entries named in this file, a NUON list or one per line",
+                None,
+            )
+            .named(
+                "since-archive",
+                SyntaxShape::Filepath,
+                "only list entries added or changed since this archive, by name and CRC",
+                None,
+            )
+            .named(
+                "min-size",
+                SyntaxShape::Filesize,
+                "only list entries at least this large",
+                None,
+            )
+            .named(
+                "max-size-entry",
+                SyntaxShape::Filesize,
+                "only list entries at most this large",
+                None,
+            )
+            .named(
+                "ext",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::String)),
+                    SyntaxShape::String,
+                ]),
+                "only list files with these extensions, like md,txt or [md txt]",
+                None,
+            )
+            .named(
+                "filter",
+                SyntaxShape::Closure(Some(vec![SyntaxShape::Record(vec![])])),
+                "only list entries this closure returns true for, given each entry",
+                None,
+            )
+            .named(
+                "max-entries",
+                SyntaxShape::Int,
+                "refuse archives with more entries than this",
+                None,
+            )
+            .named(
+                "offset",
+                SyntaxShape::Filesize,
+                "read the archive from this offset in the file, for archives embedded in others",
+                None,
+            )
+            .named(
+                "data",
+                SyntaxShape::Binary,
+                "the archive itself as binary, e.g. from a variable, instead of a file",
+                None,
+            )
+            .named(
+                "max-size",
+                SyntaxShape::Filesize,
+                "abort if a non-seekable file is larger than this, as it's buffered",
+                None,
+            )
+            .optional(
+                "file",
+                SyntaxShape::OneOf(vec![
+                    SyntaxShape::List(Box::new(SyntaxShape::Filepath)),
+                    SyntaxShape::Filepath,
+                ]),
+                "the file to list, or a list of files",
+            )
+            .rest("files", SyntaxShape::Filepath, "more files to list")
+            .input_output_types(vec![
+                (
+                    Type::Nothing,
+                    Type::Table(Box::new([
+                        ("name".into(), Type::String),
+                        ("size".into(), Type::Filesize),
+                        ("modified".into(), Type::Date),
+                        ("depth".into(), Type::Int),
+                    ])),
+                ),
+                (Type::Nothing, Type::List(Box::new(Type::String))),
+                (Type::Nothing, Type::String),
+            ])
+    }
+
+    fn description(&self) -> &str {
+        "list files in zip file, like unzip --list"
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        UnzipCommand.unzip(engine, call, input, true)
+    }
+}
+
+impl PluginCommand for UnzipCleanCommand {
+    type Plugin = UnzipPlugin;
+
+    fn name(&self) -> &str {
+        "unzip clean"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build("unzip clean").input_output_types(vec![(Type::Nothing, Type::Nothing)])
+    }
+
+    fn description(&self) -> &str {
+        "remove the directories unzipped to with unzip --spool"
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        engine: &nu_plugin::EngineInterface,
+        call: &nu_plugin::EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        remove_spool().map_err(|e| {
+            LabeledError::new(format!("Fail to remove {}", spool_root().display()))
+                .with_label(e.to_string(), call.head)
+        })?;
+        // nothing is left to keep the plugin running for
+        engine.set_gc_disabled(false)?;
+        Ok(PipelineData::Value(Value::nothing(call.head), None))
+    }
+}
+
+impl Plugin for UnzipPlugin {
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").into()
+    }
+
+    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
+        vec![
+            Box::new(UnzipCommand),
+            Box::new(UnzipListCommand),
+            Box::new(UnzipCleanCommand),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use chrono::{DateTime, Local};
+    use nu_plugin_test_support::PluginTest;
+    use nu_protocol::{IntoValue, Record, Value};
+    use std::fs;
+    use std::fs::File;
+
+    fn make_plugin_with_pwd(pwd: &Path) -> Result<PluginTest> {
+        let mut plugin = PluginTest::new("unzip", UnzipPlugin.into())?;
+
+        let pwd = Value::string(pwd.to_string_lossy(), Span::test_data());
+        plugin
+            .engine_state_mut()
+            .add_env_var("PWD".to_string(), pwd);
+
+        Ok(plugin)
+    }
+
+    fn make_plugin() -> Result<PluginTest> {
+        make_plugin_with_pwd(std::env::temp_dir().as_path())
+    }
+
+    fn set_plugin_config(plugin: &mut PluginTest, config: Value) {
+        let mut nu_config = plugin.engine_state().get_config().as_ref().clone();
+        nu_config.plugins.insert("unzip".to_string(), config);
+        plugin.engine_state_mut().set_config(nu_config);
+    }
+
+    // Get the current time
+    // convert to zip datetime and back, so that time is truncated as zip datetime
+    fn now() -> DateTime<Local> {
+        let t = Local::now();
+        let zt = zip::DateTime::try_from(t.naive_local()).unwrap();
+        let naive_dt: chrono::NaiveDateTime = zt.try_into().unwrap_or_default();
+        naive_dt
+            .and_local_timezone(Local)
+            .single()
+            .unwrap_or_default()
+    }
+
+    struct TempZipFile {
+        _path: PathBuf,
+    }
+
+    impl TempZipFile {
+        fn new(files: &[(String, Vec<u8>)], modified: DateTime<Local>) -> Result<Self> {
+            let path = testfile::generate_name();
+            let file = File::create(&path)?;
+            let modified = modified.naive_local();
+
+            let mut zip = zip::ZipWriter::new(file);
+            for (name, content) in files {
+                let options = zip::write::SimpleFileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated)
+                    .last_modified_time(modified.try_into()?);
+                // names ending with a slash are stored as directory entries
+                if name.ends_with('/') {
+                    zip.add_directory(name.as_str(), options)?;
+                } else {
+                    zip.start_file(name, options)?;
+                    zip.write_all(content)?;
+                }
+            }
+            zip.finish()?;
+            Ok(Self { _path: path })
+        }
+
+        fn path(&self) -> String {
+            self._path.as_path().to_string_lossy().to_string()
+        }
+    }
+
+    impl Drop for TempZipFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self._path);
+        }
+    }
+
+    struct TempDir {
+        _path: PathBuf,
+    }
+
+    impl TempDir {
+        fn new() -> Result<Self> {
+            let path = testfile::generate_name();
+            std::fs::create_dir_all(&path)?;
+            Ok(Self { _path: path })
+        }
+
+        fn path(&self) -> &Path {
+            self._path.as_path()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self._path).unwrap();
+        }
+    }
+
+    fn make_list_result(files: &[(String, Vec<u8>)], modified: DateTime<Local>) -> Value {
+        let items: Vec<_> = files
+            .iter()
+            .map(|(name, contents)| {
+                let item = vec![
+                    ("name".to_string(), Value::string(name, Span::test_data())),
+                    (
+                        "size".to_string(),
+                        Value::filesize(contents.len() as i64, Span::test_data()),
+                    ),
+                    (
+                        "modified".to_string(),
+                        Value::date(modified.into(), Span::test_data()),
+                    ),
+                    (
+                        "depth".to_string(),
+                        Value::int(entry_depth(name), Span::test_data()),
+                    ),
+                ];
+                Record::from_iter(item).into_value(Span::test_data())
+            })
+            .collect();
+        Value::list(items, Span::test_data())
+    }
+
+    #[test]
+    fn test_not_exists() -> Result<()> {
+        let mut plugin = make_plugin()?;
+
+        let not_exists_file = testfile::generate_name();
+        let res = plugin.eval(&format!("unzip {}", not_exists_file.to_string_lossy()));
+
+        assert!(res.is_err());
+        assert!(res
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Error opening ZIP file"),);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_empty_zip() -> Result<()> {
+        let zip_file = TempZipFile::new(&[], now())?;
+
+        let output = make_plugin()?
+            .eval(&format!("unzip -l {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::list(vec![], Span::test_data()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_simple_zip() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+
+        let output = make_plugin()?
+            .eval(&format!("unzip -l {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, make_list_result(&files, modified));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_only_files_or_dirs() -> Result<()> {
+        let dirs = vec![("a_dir/".to_string(), vec![])];
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&[dirs.clone(), files.clone()].concat(), modified)?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!("unzip -l --only-files {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, make_list_result(&files, modified));
+
+        let output = plugin
+            .eval(&format!("unzip -l --only-dirs {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, make_list_result(&dirs, modified));
+
+        let res = plugin.eval(&format!(
+            "unzip -l --only-files --only-dirs {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_empty_zip() -> Result<()> {
+        let zip_file = TempZipFile::new(&[], now())?;
+        let current_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::nothing(Span::test_data()));
+
+        assert!(fs::read_dir(current_dir.path()).unwrap().next().is_none());
+
+        Ok(())
+    }
+
+    fn check_extracted_files(files: &[(String, Vec<u8>)], directory: &Path) {
+        for (file_name, file_contents) in files {
+            let file_path = directory.join(file_name);
+            if file_name.ends_with('/') {
+                assert!(file_path.is_dir());
+                continue;
+            }
+            assert!(file_path.exists());
+            assert_eq!(
+                &fs::read(file_path).unwrap(),
+                file_contents,
+                "File contents differ for {}",
+                file_name
+            );
+        }
+    }
+
+    #[test]
+    fn test_unzip_simple_zip() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::nothing(Span::test_data()));
+
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_with_folder() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::nothing(Span::test_data()));
+
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_force() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let cmd = format!("unzip {}", zip_file.path());
+        plugin.eval(&cmd)?;
+
+        let res = plugin.eval(&cmd);
+
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("already exists"));
+
+        plugin.eval(&(cmd + " -f"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_simple_zip_to_specified_dir() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+        let dest_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "unzip -d {} {}",
+                dest_dir.path().to_string_lossy(),
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::nothing(Span::test_data()));
+
+        assert!(fs::read_dir(current_dir.path()).unwrap().next().is_none());
+        check_extracted_files(&files, dest_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_add_prefix() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/".to_string(), vec![]),
+            ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --add-prefix vendor/lib {}",
+            zip_file.path()
+        ))?;
+
+        assert!(current_dir.path().join("vendor/lib/a_dir").is_dir());
+        check_extracted_files(
+            &[
+                ("file1.txt".to_string(), b"content1".to_vec()),
+                ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+            ],
+            &current_dir.path().join("vendor/lib"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_add_prefix_with_strip_components() -> Result<()> {
+        let files = vec![
+            ("root/".to_string(), vec![]),
+            ("root/file1.txt".to_string(), b"content1".to_vec()),
+            (
+                "root/a_dir/file2.txt".to_string(),
+                b"hello content2".to_vec(),
+            ),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --strip-components 1 --add-prefix vendor {}",
+            zip_file.path()
+        ))?;
+
+        assert!(!current_dir.path().join("root").exists());
+        assert!(!current_dir.path().join("vendor/root").exists());
+        check_extracted_files(
+            &[
+                ("file1.txt".to_string(), b"content1".to_vec()),
+                ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+            ],
+            &current_dir.path().join("vendor"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_strip_trailing() -> Result<()> {
+        let files = vec![
+            ("root/".to_string(), vec![]),
+            ("root/file1.txt".to_string(), b"content1".to_vec()),
+            ("root/a_dir/".to_string(), vec![]),
+            (
+                "root/a_dir/file2.txt".to_string(),
+                b"hello content2".to_vec(),
+            ),
+            ("root/a_dir/b_dir/".to_string(), vec![]),
+            (
+                "root/a_dir/b_dir/file3.txt".to_string(),
+                b"content3".to_vec(),
+            ),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --strip-trailing 1 {}", zip_file.path()))?;
+
+        assert!(!current_dir.path().join("root/a_dir/b_dir").exists());
+        check_extracted_files(
+            &[
+                ("file1.txt".to_string(), b"content1".to_vec()),
+                ("root/file2.txt".to_string(), b"hello content2".to_vec()),
+                ("root/a_dir/file3.txt".to_string(), b"content3".to_vec()),
+            ],
+            current_dir.path(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_within() -> Result<()> {
+        let files = vec![
+            ("root/".to_string(), vec![]),
+            ("root/file1.txt".to_string(), b"content1".to_vec()),
+            ("root/docs/".to_string(), vec![]),
+            ("root/docs/guide.md".to_string(), b"guide".to_vec()),
+            ("root/docs/api/".to_string(), vec![]),
+            ("root/docs/api/index.md".to_string(), b"index".to_vec()),
+            ("root/docsite.md".to_string(), b"site".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --within ./root/docs/ {}", zip_file.path()))?;
+
+        check_extracted_files(
+            &[
+                ("guide.md".to_string(), b"guide".to_vec()),
+                ("api/index.md".to_string(), b"index".to_vec()),
+            ],
+            current_dir.path(),
+        );
+        assert!(!current_dir.path().join("root").exists());
+        assert!(!current_dir.path().join("docsite.md").exists());
+        assert!(!current_dir.path().join("file1.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_path_transform() -> Result<()> {
+        // PluginTest can't evaluate closures, so check what is made of their results
+        let mut log = Log {
+            quiet: true,
+            ..Default::default()
+        };
+        let mut transformed =
+            |result| transformed_path("src/main.rs", result, Span::test_data(), &mut log);
+
+        assert_eq!(
+            transformed(Ok(Value::test_string("code/main.rs"))),
+            Ok(PathBuf::from("code/main.rs"))
+        );
+        assert_eq!(
+            transformed(Ok(Value::test_nothing())),
+            Err(SkipReason::Filtered)
+        );
+        assert_eq!(
+            transformed(Ok(Value::test_string("../evil.txt"))),
+            Err(SkipReason::Unsafe)
+        );
+        assert_eq!(
+            transformed(Ok(Value::test_string(""))),
+            Err(SkipReason::Unsafe)
+        );
+        assert_eq!(transformed(Ok(Value::test_int(1))), Err(SkipReason::Other));
+        assert_eq!(
+            transformed(Err(ShellError::NushellFailed {
+                msg: "failed".to_string()
+            })),
+            Err(SkipReason::Other)
+        );
+        assert_eq!(
+            log.warnings[0],
+            "Skipping src/main.rs: --path-transform returned ../evil.txt, which isn't a \
+             relative path inside the target directory"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_add_prefix_traversal() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let res = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --add-prefix ../escape {}", zip_file.path()));
+
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("Invalid prefix"));
+        assert!(fs::read_dir(current_dir.path()).unwrap().next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_warns_on_traversal() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("../evil.txt".to_string(), b"evil".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let mut archive = ZipArchive::new(File::open(zip_file.path())?)?;
+        let mut log = Log {
+            quiet: true,
+            ..Default::default()
+        };
+        UnzipCommand.unzip_file(
+            Span::test_data(),
+            &mut archive,
+            &ExtractOptions::default(),
+            current_dir.path(),
+            &ArchiveRun::default(),
+            &mut log,
+        )?;
+
+        check_extracted_files(&files[..1], current_dir.path());
+        assert!(!current_dir.path().join("../evil.txt").exists());
+        assert_eq!(log.warnings.len(), 1);
+        assert!(log.warnings[0].contains("../evil.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_depth() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/".to_string(), vec![]),
+            (
+                "a_dir/b_dir/file2.txt".to_string(),
+                b"hello content2".to_vec(),
+            ),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l {}).depth", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::list(
+                vec![
+                    Value::int(0, Span::test_data()),
+                    Value::int(0, Span::test_data()),
+                    Value::int(2, Span::test_data()),
+                ],
+                Span::test_data()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_max_size() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let res = plugin.eval(&format!("unzip --max-size 10b {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("larger than 10 bytes"));
+
+        plugin.eval(&format!("unzip -f --max-size 1kb {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_unzip_write_failure_cleanup() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        // writes to /dev/full fail as if the disk was full
+        let out_path = current_dir.path().join("file1.txt");
+        std::os::unix::fs::symlink("/dev/full", &out_path)?;
+
+        let res = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip -f {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(fs::symlink_metadata(&out_path).is_err());
+
+        Ok(())
+    }
+
+    /// Create a FIFO in a fresh directory and feed `contents` into it from a background thread
+    #[cfg(unix)]
+    fn make_fifo(contents: Vec<u8>) -> Result<(TempDir, PathBuf)> {
+        let dir = TempDir::new()?;
+        let fifo = dir.path().join("archive.zip");
+        let status = std::process::Command::new("mkfifo").arg(&fifo).status()?;
+        anyhow::ensure!(status.success(), "mkfifo failed");
+
+        let writer_path = fifo.clone();
+        std::thread::spawn(move || {
+            // the reader may stop early, which makes this write fail
+            let _ = fs::write(writer_path, contents);
+        });
+
+        Ok((dir, fifo))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_from_fifo() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let (_dir, fifo) = make_fifo(fs::read(zip_file.path())?)?;
+
+        let output = make_plugin()?
+            .eval(&format!("unzip -l {}", fifo.to_string_lossy()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, make_list_result(&files, modified));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fifo_larger_than_max_size() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let (_dir, fifo) = make_fifo(fs::read(zip_file.path())?)?;
+
+        let res = make_plugin()?.eval(&format!(
+            "unzip -l --max-size 16b {}",
+            fifo.to_string_lossy()
+        ));
+
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("ZIP stream is larger than 16 bytes"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_epoch() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!(
+                "(unzip -l --epoch {}).modified_epoch",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::list(
+                vec![Value::int(modified.timestamp(), Span::test_data())],
+                Span::test_data()
+            )
+        );
+
+        // the default schema is unchanged
+        let output = plugin
+            .eval(&format!("unzip -l {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, make_list_result(&files, modified));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_only_and_dot_slash_names() -> Result<()> {
+        let files = vec![
+            (".".to_string(), b"dot".to_vec()),
+            ("./".to_string(), vec![]),
+            ("/".to_string(), vec![]),
+            ("./file1.txt".to_string(), b"content1".to_vec()),
+            ("./a_dir/".to_string(), vec![]),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let output = plugin
+            .eval(&format!("unzip -l {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        let expected = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/".to_string(), vec![]),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        assert_eq!(output, make_list_result(&expected, modified));
+
+        plugin.eval(&format!("unzip {}", zip_file.path()))?;
+        check_extracted_files(
+            &[
+                ("file1.txt".to_string(), b"content1".to_vec()),
+                ("file2.txt".to_string(), b"hello content2".to_vec()),
+            ],
+            current_dir.path(),
+        );
+        assert!(current_dir.path().join("a_dir").is_dir());
+        assert_eq!(fs::read_dir(current_dir.path())?.count(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_config_defaults() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let config_dir = TempDir::new()?;
+        let flag_dir = TempDir::new()?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+        let config = Record::from_iter([
+            ("force".to_string(), Value::test_bool(true)),
+            (
+                "dir".to_string(),
+                Value::test_string(config_dir.path().to_string_lossy()),
+            ),
+        ]);
+        set_plugin_config(&mut plugin, Value::test_record(config));
+
+        // twice, as force is enabled by the config
+        let cmd = format!("unzip {}", zip_file.path());
+        plugin.eval(&cmd)?;
+        plugin.eval(&cmd)?;
+        check_extracted_files(&files, config_dir.path());
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        // --dir overrides the configured directory
+        plugin.eval(&format!(
+            "unzip -d {} {}",
+            flag_dir.path().to_string_lossy(),
+            zip_file.path()
+        ))?;
+        check_extracted_files(&files, flag_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_config_unknown_option() -> Result<()> {
+        let zip_file = TempZipFile::new(&[], now())?;
+        let mut plugin = make_plugin()?;
+        let config = Record::from_iter([("colour".to_string(), Value::test_bool(true))]);
+        set_plugin_config(&mut plugin, Value::test_record(config));
+
+        let res = plugin.eval(&format!("unzip -l {}", zip_file.path()));
+
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown unzip config option colour"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_report_conflicts() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        // no conflicts, so the archive is extracted
+        let output = plugin
+            .eval(&format!("unzip --report-conflicts {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, Value::test_list(vec![]));
+        check_extracted_files(&files, current_dir.path());
+
+        fs::write(current_dir.path().join("file2.txt"), b"changed content")?;
+
+        let output = plugin
+            .eval(&format!(
+                "(unzip --report-conflicts {}).differs",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_bool(false), Value::test_bool(true)])
+        );
+        // nothing was extracted
+        assert_eq!(
+            fs::read(current_dir.path().join("file2.txt"))?,
+            b"changed content"
+        );
+
+        plugin.eval(&format!("unzip -f --report-conflicts {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_multiple_archives() -> Result<()> {
+        let files1 = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let files2 = vec![("file2.txt".to_string(), b"hello content2".to_vec())];
+        let zip_file1 = TempZipFile::new(&files1, now())?;
+        let zip_file2 = TempZipFile::new(&files2, now())?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!(
+                "(unzip -l {} {}).source",
+                zip_file1.path(),
+                zip_file2.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string(zip_file1.path()),
+                Value::test_string(zip_file2.path()),
+            ])
+        );
+
+        let output = plugin
+            .eval(&format!(
+                "(unzip -l --with-source {}).source",
+                zip_file1.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_string(zip_file1.path())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_multiple_archives() -> Result<()> {
+        let files1 = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let files2 = vec![("a_dir/file2.txt".to_string(), b"hello content2".to_vec())];
+        let zip_file1 = TempZipFile::new(&files1, now())?;
+        let zip_file2 = TempZipFile::new(&files2, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip {} {}",
+            zip_file1.path(),
+            zip_file2.path()
+        ))?;
+
+        check_extracted_files(&files1, current_dir.path());
+        check_extracted_files(&files2, current_dir.path());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_from_fifo() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/".to_string(), vec![]),
+            ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let (_dir, fifo) = make_fifo(fs::read(zip_file.path())?)?;
+        let current_dir = TempDir::new()?;
+
+        // the archive is larger than --max-size, so it must not be buffered
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --max-size 100b {}", fifo.to_string_lossy()))?;
+
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_from_fifo_strict() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("/tmp/evil.txt".to_string(), b"evil".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let (_dir, fifo) = make_fifo(fs::read(zip_file.path())?)?;
+        let current_dir = TempDir::new()?;
+
+        let res = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --strict {}", fifo.to_string_lossy()));
+
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Archive contains absolute path /tmp/evil.txt"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_verbose() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        // made by NTFS 6.3, needing 2.0, whatever the writer stores
+        let mut bytes = fs::read(zip_file.path())?;
+        let central = bytes
+            .windows(4)
+            .position(|window| window == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        bytes[central + 4..central + 8].copy_from_slice(&[63, 10, 20, 0]);
+        fs::write(zip_file.path(), bytes)?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "let rows = unzip -l -v {}; \
+                 [$rows.0.made_by_version $rows.0.host_os $rows.0.version_needed]",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("6.3"),
+                Value::test_string("ntfs"),
+                Value::test_string("2.0"),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_flags() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        zip.start_file("ascii.txt", zip::write::SimpleFileOptions::default())?;
+        zip.start_file("ünïcode.txt", zip::write::SimpleFileOptions::default())?;
+        zip.finish()?;
+
+        let flags = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).flags", path.display()))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        let expected = |raw: &str, names: Vec<&str>| {
+            Value::test_record(Record::from_iter([
+                ("raw".to_string(), Value::test_string(raw)),
+                (
+                    "names".to_string(),
+                    Value::test_list(names.into_iter().map(Value::test_string).collect()),
+                ),
+            ]))
+        };
+        assert_eq!(
+            flags,
+            Value::test_list(vec![
+                expected("0x0000", vec![]),
+                expected("0x0800", vec!["utf8"]),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_os_names() {
+        let header = |version_made_by| CentralHeader {
+            version_made_by,
+            version_needed: 20,
+            ..Default::default()
+        };
+        assert_eq!(header(20).host_os(), "fat");
+        assert_eq!(header((10 << 8) | 20).host_os(), "ntfs");
+        assert_eq!(header((63 << 8) | 20).host_os(), "unknown(63)");
+        assert_eq!(header((3 << 8) | 63).made_by_version(), "6.3");
+        assert_eq!(header(20).version_needed(), "2.0");
+        let zip64 = CentralHeader {
+            version_made_by: 63,
+            version_needed: 45,
+            ..Default::default()
+        };
+        assert_eq!(zip64.version_needed(), "4.5 (zip64)");
+    }
+
+    #[test]
+    fn test_unzip_absolute_path() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("/tmp/evil.txt".to_string(), b"evil".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let res = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --strict {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Archive contains absolute path /tmp/evil.txt"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        let mut archive = ZipArchive::new(File::open(zip_file.path())?)?;
+        let mut log = Log {
+            quiet: true,
+            ..Default::default()
+        };
+        UnzipCommand.unzip_file(
+            Span::test_data(),
+            &mut archive,
+            &ExtractOptions::default(),
+            current_dir.path(),
+            &ArchiveRun::default(),
+            &mut log,
+        )?;
+        check_extracted_files(&files[..1], current_dir.path());
+        assert_eq!(
+            log.warnings,
+            vec!["Skipping /tmp/evil.txt: path is absolute"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_absolute_name() {
+        assert!(is_absolute_name("/etc/cron.d/evil"));
+        assert!(is_absolute_name("\\windows\\system32"));
+        assert!(is_absolute_name("C:/windows"));
+        assert!(!is_absolute_name("a_dir/file.txt"));
+        assert!(!is_absolute_name("../file.txt"));
+    }
+
+    #[test]
+    fn test_regex_filter() -> Result<()> {
+        let files = vec![
+            ("src/lib.rs".to_string(), b"lib".to_vec()),
+            ("src/lib_test.rs".to_string(), b"test".to_vec()),
+            ("README.md".to_string(), b"readme".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let output = plugin
+            .eval(&format!(
+                "unzip -l --regex '^(?!.*test).*\\.rs$' {}",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, make_list_result(&files[..1], modified));
+
+        plugin.eval(&format!("unzip --regex 'md$' {}", zip_file.path()))?;
+        check_extracted_files(&files[2..], current_dir.path());
+        assert!(!current_dir.path().join("src").exists());
+
+        let res = plugin.eval(&format!("unzip -l --regex '(' {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("Invalid regex ("));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_dedupe_by_crc() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"same content".to_vec()),
+            ("a_dir/file2.txt".to_string(), b"same content".to_vec()),
+            ("file3.txt".to_string(), b"other content".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --dedupe-by-crc {}", zip_file.path()))?;
+
+        check_extracted_files(&files, current_dir.path());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |name: &str| fs::metadata(current_dir.path().join(name)).unwrap().ino();
+            assert_eq!(inode("file1.txt"), inode("a_dir/file2.txt"));
+            assert_ne!(inode("file1.txt"), inode("file3.txt"));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_timeout() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let res = plugin.eval(&format!("unzip --timeout 0sec {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("Unzipping timed out"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        plugin.eval(&format!("unzip --timeout 1min {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_dir_size_is_zero() -> Result<()> {
+        let files = vec![("dir/".to_string(), vec![])];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        // pretend the archiver stored a nonzero uncompressed size on the directory
+        let mut bytes = fs::read(zip_file.path())?;
+        let header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[header + 24..header + 28].copy_from_slice(&42u32.to_le_bytes());
+        fs::write(zip_file.path(), bytes)?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l {}).size", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::test_list(vec![Value::test_filesize(0)]));
+
+        Ok(())
+    }
+
+    /// AES encrypted archive of `(name, password)` entries, each containing its own name
+    fn make_encrypted_zip(entries: &[(&str, &str)]) -> Result<String> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for (name, password) in entries {
+            let options = zip::write::SimpleFileOptions::default()
+                .with_aes_encryption(zip::AesMode::Aes256, password);
+            zip.start_file(*name, options)?;
+            zip.write_all(name.as_bytes())?;
+        }
+        zip.finish()?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn test_unzip_password_list() -> Result<()> {
+        let path = make_encrypted_zip(&[("file1.txt", "first"), ("file2.txt", "second")])?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        plugin.eval(&format!("unzip -q -p second {path}"))?;
+        assert_eq!(fs::read_dir(current_dir.path())?.count(), 1);
+        assert!(current_dir.path().join("file2.txt").exists());
+
+        plugin.eval(&format!("unzip -f -p [wrong first second] {path}"))?;
+        check_extracted_files(
+            &[
+                ("file1.txt".to_string(), b"file1.txt".to_vec()),
+                ("file2.txt".to_string(), b"file2.txt".to_vec()),
+            ],
+            current_dir.path(),
+        );
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_ntfs_timestamp() -> Result<()> {
+        let modified = chrono::DateTime::from_timestamp(1_577_934_245, 123_456_700).unwrap();
+        let ticks = (modified.timestamp() * 10_000_000
+            + modified.timestamp_subsec_nanos() as i64 / 100
+            + NTFS_EPOCH_OFFSET) as u64;
+        let mut field = Vec::new();
+        field.extend_from_slice(&0u32.to_le_bytes());
+        field.extend_from_slice(&1u16.to_le_bytes());
+        field.extend_from_slice(&24u16.to_le_bytes());
+        for _ in 0..3 {
+            field.extend_from_slice(&ticks.to_le_bytes());
+        }
+
+        // the writer refuses the reserved NTFS header id, so use a placeholder and patch it
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let mut options = zip::write::FullFileOptions::default();
+        options.add_extra_data(0xcafe, field.into_boxed_slice(), false)?;
+        zip.start_file("file1.txt", options)?;
+        zip.write_all(b"content1")?;
+        zip.finish()?;
+        let mut bytes = fs::read(&path)?;
+        let placeholder = [0xfe, 0xca, 32, 0];
+        for i in 0..bytes.len() - 4 {
+            if bytes[i..i + 4] == placeholder {
+                bytes[i..i + 2].copy_from_slice(&0x000au16.to_le_bytes());
+            }
+        }
+        fs::write(&path, bytes)?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l {}).0.modified", path.display()))?
+            .into_value(Span::test_data())?;
+        let quick = make_plugin()?
+            .eval(&format!("(unzip -l --quick {}).0.modified", path.display()))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(output.as_date()?, modified);
+        // the DOS time has no sub-second part
+        assert_eq!(quick.as_date()?.timestamp_subsec_nanos(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_time_source() -> Result<()> {
+        let ntfs = chrono::DateTime::from_timestamp(1_577_934_245, 0).unwrap();
+        let extended = chrono::DateTime::from_timestamp(1_600_000_000, 0).unwrap();
+        let ticks = (ntfs.timestamp() * 10_000_000 + NTFS_EPOCH_OFFSET) as u64;
+        let mut ntfs_field = Vec::new();
+        ntfs_field.extend_from_slice(&0u32.to_le_bytes());
+        ntfs_field.extend_from_slice(&1u16.to_le_bytes());
+        ntfs_field.extend_from_slice(&24u16.to_le_bytes());
+        for _ in 0..3 {
+            ntfs_field.extend_from_slice(&ticks.to_le_bytes());
+        }
+        // flags with only the modification time, then the time itself
+        let mut extended_field = vec![1];
+        extended_field.extend_from_slice(&(extended.timestamp() as u32).to_le_bytes());
+
+        // the writer refuses reserved header ids, so use placeholders and patch them
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        // a DOS time older than both, or newest would pick it
+        let mut options = zip::write::FullFileOptions::default()
+            .last_modified_time(zip::DateTime::from_date_and_time(2000, 1, 1, 0, 0, 0).unwrap());
+        options.add_extra_data(0xcafe, ntfs_field.into_boxed_slice(), false)?;
+        options.add_extra_data(0xbeef, extended_field.into_boxed_slice(), false)?;
+        zip.start_file("file1.txt", options)?;
+        zip.write_all(b"content1")?;
+        zip.finish()?;
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 4 {
+            match bytes[i..i + 4] {
+                [0xfe, 0xca, 32, 0] => bytes[i..i + 2].copy_from_slice(&0x000au16.to_le_bytes()),
+                [0xef, 0xbe, 5, 0] => bytes[i..i + 2].copy_from_slice(&0x5455u16.to_le_bytes()),
+                _ => {}
+            }
+        }
+        fs::write(&path, bytes)?;
+
+        let modified = |flags: &str| -> Result<_> {
+            let output = make_plugin()?
+                .eval(&format!("(unzip -l {flags} {}).0.modified", path.display()))?
+                .into_value(Span::test_data())?;
+            Ok(output.as_date()?)
+        };
+        // NTFS is preferred by default, even when older
+        assert_eq!(modified("")?, ntfs);
+        assert_eq!(modified("--time-source ntfs")?, ntfs);
+        assert_eq!(modified("--time-source extended")?, extended);
+        assert_eq!(modified("--time-source newest")?, extended);
+        assert!(modified("--time-source atime").is_err());
+        fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_local() -> Result<()> {
+        let files = vec![
+            ("dir/".to_string(), vec![]),
+            ("dir/file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"hello content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        // rename an entry in its central header only, as a tampered archive may
+        let mut bytes = fs::read(zip_file.path())?;
+        let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        for i in central..bytes.len() - 9 {
+            if &bytes[i..i + 9] == b"file2.txt" {
+                bytes[i..i + 9].copy_from_slice(b"file3.txt");
+            }
+        }
+        fs::write(zip_file.path(), bytes)?;
+
+        let list = |flags: &str| -> Result<Value> {
+            Ok(make_plugin()?
+                .eval(&format!("(unzip list {flags} {}).name", zip_file.path()))?
+                .into_value(Span::test_data())?)
+        };
+        let names = |names: [&str; 3]| {
+            Value::test_list(names.into_iter().map(Value::test_string).collect())
+        };
+
+        assert_eq!(list("")?, names(["dir/", "dir/file1.txt", "file3.txt"]));
+        assert_eq!(
+            list("--local")?,
+            names(["dir/", "dir/file1.txt", "file2.txt"])
+        );
+
+        let crc32 = make_plugin()?
+            .eval(&format!("(unzip list --local {}).2.crc32", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            crc32,
+            Value::test_int(crc32fast::hash(b"hello content2") as i64)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_preview() -> Result<()> {
+        let files = vec![
+            ("dir/".to_string(), vec![]),
+            ("text.txt".to_string(), b"hello world".to_vec()),
+            ("accent.txt".to_string(), "caf\u{e9}".as_bytes().to_vec()),
+            ("data.bin".to_string(), vec![0, 1, 2, 3]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let preview = |flags: &str| -> Result<Value> {
+            Ok(make_plugin()?
+                .eval(&format!(
+                    "(unzip -l --preview 4 {flags} {}).preview",
+                    zip_file.path()
+                ))?
+                .into_value(Span::test_data())?)
+        };
+        // the é is cut in half by the limit
+        let expected = Value::test_list(vec![
+            Value::test_nothing(),
+            Value::test_string("hell"),
+            Value::test_string("caf"),
+            Value::test_string("<binary>"),
+        ]);
+
+        assert_eq!(preview("")?, expected);
+        assert_eq!(preview("--local")?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_names() -> Result<()> {
+        let files = vec![
+            ("dir/".to_string(), vec![]),
+            ("dir/file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.log".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!(
+                "unzip --names --regex 'txt$|/$' {}",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("dir/"),
+                Value::test_string("dir/file1.txt"),
+            ])
+        );
+
+        let output = plugin
+            .eval(&format!("unzip --print0 {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output, Value::test_string("dir/\0dir/file1.txt\0file2.log"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_implausible_size() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        // claim an uncompressed size of almost 4GiB in the central directory
+        let mut bytes = fs::read(zip_file.path())?;
+        let header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[header + 24..header + 28].copy_from_slice(&0xfff0_0000u32.to_le_bytes());
+        fs::write(zip_file.path(), bytes)?;
+
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let res = plugin.eval(&format!("unzip --strict {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Archive entry file1.txt has an implausible size"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        plugin.eval(&format!("unzip -q {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_implausible_size_from_fifo() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let mut bytes = fs::read(zip_file.path())?;
+        let header = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[header + 24..header + 28].copy_from_slice(&0xfff0_0000u32.to_le_bytes());
+        let (_dir, fifo) = make_fifo(bytes)?;
+        let current_dir = TempDir::new()?;
+
+        let res = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --strict {}", fifo.to_string_lossy()));
+
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Archive entry file1.txt has an implausible size"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_encryption() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        zip.start_file("plain.txt", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"plain")?;
+        let options = zip::write::SimpleFileOptions::default()
+            .with_aes_encryption(zip::AesMode::Aes256, "secret");
+        zip.start_file("secret.txt", options)?;
+        zip.write_all(b"secret")?;
+        zip.finish()?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).encryption", path.display()))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("none"),
+                Value::test_string("aes256"),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_fsync() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("a_dir/".to_string(), vec![]),
+            ("a_dir/file2.txt".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --fsync --atomic {}", zip_file.path()))?;
+
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_crc_mismatch() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("bad.txt", options)?;
+        zip.write_all(b"original data")?;
+        zip.start_file("good.txt", options)?;
+        zip.write_all(b"content")?;
+        zip.finish()?;
+        // stored data can be changed in place, leaving the CRC32 stale
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 13 {
+            if &bytes[i..i + 13] == b"original data" {
+                bytes[i] = b'O';
+            }
+        }
+        fs::write(&path, bytes)?;
+        let current_dir = TempDir::new()?;
+
+        let res =
+            make_plugin_with_pwd(current_dir.path())?.eval(&format!("unzip {}", path.display()));
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Data corruption (CRC mismatch) in bad.txt"));
+        assert!(!current_dir.path().join("bad.txt").exists());
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "(unzip -q --skip-errors --report {}).corrupted",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(output, Value::test_int(1));
+        assert!(!current_dir.path().join("bad.txt").exists());
+        check_extracted_files(
+            &[("good.txt".to_string(), b"content".to_vec())],
+            current_dir.path(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_cache() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("data.txt", options)?;
+        zip.write_all(b"original data")?;
+        zip.finish()?;
+        let cache_dir = TempDir::new()?;
+
+        let verify = |flags: &str| -> Result<Value> {
+            Ok(make_plugin()?
+                .eval(&format!(
+                    "(unzip --verify --cache-dir {} {flags} {}).0",
+                    cache_dir.path().display(),
+                    path.display()
+                ))?
+                .into_value(Span::test_data())?
+                .into_record()
+                .map(|mut record| {
+                    record.remove("path");
+                    Value::test_record(record)
+                })?)
+        };
+        let row = |ok: bool, entries: Option<i64>, failed: Vec<Value>, cached: bool| {
+            Value::test_record(Record::from_iter([
+                ("ok".to_string(), Value::test_bool(ok)),
+                (
+                    "entries".to_string(),
+                    entries.map_or(Value::test_nothing(), Value::test_int),
+                ),
+                ("failed".to_string(), Value::test_list(failed)),
+                ("cached".to_string(), Value::test_bool(cached)),
+            ]))
+        };
+
+        assert_eq!(verify("")?, row(true, Some(1), vec![], false));
+        assert_eq!(verify("")?, row(true, None, vec![], true));
+        assert_eq!(verify("--no-cache")?, row(true, Some(1), vec![], false));
+
+        // corrupt the stored data, and make sure the change shows in the modification time
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 13 {
+            if &bytes[i..i + 13] == b"original data" {
+                bytes[i] = b'O';
+            }
+        }
+        fs::write(&path, bytes)?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(10))?;
+
+        let failure = Value::test_record(Record::from_iter([
+            ("name".to_string(), Value::test_string("data.txt")),
+            (
+                "error".to_string(),
+                Value::test_string("data corruption (CRC mismatch)"),
+            ),
+        ]));
+        assert_eq!(verify("-q")?, row(false, Some(1), vec![failure], false));
+        fs::remove_file(path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_repair() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("dir/data.txt", options)?;
+        zip.write_all(b"original data")?;
+        zip.start_file("fine.txt", options)?;
+        zip.write_all(b"fine")?;
+        zip.finish()?;
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 13 {
+            if &bytes[i..i + 13] == b"original data" {
+                bytes[i] = b'O';
+            }
+        }
+        fs::write(&path, bytes)?;
+        let current_dir = TempDir::new()?;
+
+        let failed = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "let failed = (unzip --verify --no-cache --repair -q {}).0.failed; \
+                 [$failed.name $failed.recovered]",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            failed,
+            Value::test_list(vec![
+                Value::test_list(vec![Value::test_string("dir/data.txt")]),
+                Value::test_list(vec![Value::test_filesize(13)]),
+            ])
+        );
+        assert_eq!(
+            fs::read_to_string(current_dir.path().join("dir/data.txt.partial"))?,
+            "Original data"
+        );
+        assert!(!current_dir.path().join("fine.txt.partial").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_report() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for name in ["plain.txt", "notes.md", "../evil.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"plain")?;
+        }
+        let options = zip::write::SimpleFileOptions::default()
+            .with_aes_encryption(zip::AesMode::Aes256, "secret");
+        zip.start_file("secret.txt", options)?;
+        zip.write_all(b"secret")?;
+        zip.finish()?;
+        let current_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --report --regex 'txt$' {}", path.display()))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            output,
+            Value::test_record(Record::from_iter(
+                [
+                    ("extracted", 1),
+                    ("skipped_encrypted", 1),
+                    ("skipped_unsupported", 0),
+                    ("skipped_unsafe", 1),
+                    ("filtered", 1),
+                    ("refused", 0),
+                    ("corrupted", 0),
+                    ("skipped_other", 0),
+                ]
+                .map(|(key, count)| (key.to_string(), Value::test_int(count)))
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unzip_confine() -> Result<()> {
+        let files = vec![
+            ("ok.txt".to_string(), b"ok".to_vec()),
+            ("link/evil.txt".to_string(), b"evil".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let outside = TempDir::new()?;
+        std::os::unix::fs::symlink(outside.path(), current_dir.path().join("link"))?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --confine {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::test_int(1));
+        assert!(current_dir.path().join("ok.txt").exists());
+        assert!(!outside.path().join("evil.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_out_template() -> Result<()> {
+        use chrono::Datelike;
+
+        let files = vec![
+            ("logs/a.log".to_string(), b"a".to_vec()),
+            ("logs/old/b.log".to_string(), b"b".to_vec()),
+        ];
+        let modified = now();
+        let zip_file = TempZipFile::new(&files, modified)?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        plugin.eval(&format!(
+            "unzip --out-template '{{dir}}/{{year}}/{{name}}' {}",
+            zip_file.path()
+        ))?;
+        let year_dir = current_dir.path().join(format!("{:04}", modified.year()));
+        assert_eq!(fs::read(year_dir.join("a.log"))?, b"a");
+        assert_eq!(fs::read(year_dir.join("b.log"))?, b"b");
+
+        let res = plugin.eval(&format!(
+            "unzip --out-template '{{dir}}/{{nam}}' {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown placeholder {nam} in template"));
+
+        let other_dir = TempDir::new()?;
+        make_plugin_with_pwd(other_dir.path())?.eval(&format!(
+            "unzip -q --out-template '../{{name}}' {}",
+            zip_file.path()
+        ))?;
+        assert!(fs::read_dir(other_dir.path())?.next().is_none());
+        assert!(!other_dir.path().parent().unwrap().join("a.log").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_to() -> Result<()> {
+        let files = vec![
+            ("same.txt".to_string(), b"same".to_vec()),
+            ("changed.txt".to_string(), b"new content".to_vec()),
+            ("dir/added.txt".to_string(), b"added".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let existing = TempDir::new()?;
+        fs::write(existing.path().join("same.txt"), b"same")?;
+        fs::write(existing.path().join("changed.txt"), b"old content")?;
+        fs::create_dir(existing.path().join("dir"))?;
+        fs::write(existing.path().join("dir/removed.txt"), b"removed")?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "let rows = unzip --compare-to {} {}; [$rows.name $rows.status]",
+                existing.path().display(),
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        let strings = |values: &[&str]| {
+            Value::test_list(values.iter().map(|v| Value::test_string(*v)).collect())
+        };
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                strings(&[
+                    "same.txt",
+                    "changed.txt",
+                    "dir/added.txt",
+                    "dir/removed.txt"
+                ]),
+                strings(&["same", "changed", "added", "removed"]),
+            ])
+        );
+        assert!(!existing.path().join("dir/added.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_range() -> Result<()> {
+        let content: Vec<u8> = (0..=255).cycle().take(10000).collect();
+        let files = vec![
+            ("big.bin".to_string(), content.clone()),
+            ("small.txt".to_string(), b"small".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!(
+                "unzip --entry big.bin --range 5000..5009 {}",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output.as_binary()?, &content[5000..5010]);
+
+        let output = plugin
+            .eval(&format!("unzip --entry small.txt {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(output.as_binary()?, b"small");
+
+        let res = plugin.eval(&format!(
+            "unzip --entry small.txt --range 0..10 {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("is outside of entry small.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_password_precedence() -> Result<()> {
+        let path = make_encrypted_zip(&[("env.txt", "env"), ("file.txt", "file")])?;
+        let password_dir = TempDir::new()?;
+        let password_file = password_dir.path().join("passwords");
+        fs::write(&password_file, "file\n")?;
+
+        let unzip = |args: &str| -> Result<Vec<String>> {
+            let current_dir = TempDir::new()?;
+            make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+                "$env.NU_UNZIP_PASSWORD = 'env'; unzip -q {args} {path}"
+            ))?;
+            let mut names: Vec<_> = fs::read_dir(current_dir.path())?
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+                .collect::<Result<_>>()?;
+            names.sort();
+            Ok(names)
+        };
+
+        assert_eq!(unzip("")?, vec!["env.txt"]);
+        let file_arg = format!("--password-file {}", password_file.display());
+        assert_eq!(unzip(&file_arg)?, vec!["file.txt"]);
+        assert_eq!(unzip(&format!("-p env {file_arg}"))?, vec!["env.txt"]);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for (name, method) in [
+            ("stored.txt", zip::CompressionMethod::Stored),
+            ("deflated.txt", zip::CompressionMethod::Deflated),
+            ("zstd.txt", zip::CompressionMethod::Zstd),
+        ] {
+            let options = zip::write::SimpleFileOptions::default().compression_method(method);
+            zip.start_file(name, options)?;
+            zip.write_all(&[b'a'; 1000])?;
+        }
+        zip.finish()?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "let stats = unzip --stats {}; \
+                 [$stats.entries $stats.stored $stats.deflated $stats.zstd $stats.other \
+                  $stats.total_uncompressed ($stats.overall_ratio > 1.0)]",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_int(3),
+                Value::test_int(1),
+                Value::test_int(1),
+                Value::test_int(1),
+                Value::test_int(0),
+                Value::test_filesize(3000),
+                Value::test_bool(true),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_validity() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), vec![b'a'; 1000])];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let bytes = fs::read(zip_file.path())?;
+        // an incomplete download, cut before the central directory
+        let truncated = testfile::generate_name();
+        fs::write(&truncated, &bytes[..bytes.len() / 2])?;
+        let not_zip = testfile::generate_name();
+        fs::write(&not_zip, "not a zip file")?;
+
+        let validity = |path: &str| -> Result<Value> {
+            Ok(make_plugin()?
+                .eval(&format!(
+                    "let stats = unzip --stats -q {path}; [$stats.valid $stats.truncated]"
+                ))?
+                .into_value(Span::test_data())?)
+        };
+        let expected = |valid, truncated| {
+            Value::test_list(vec![Value::test_bool(valid), Value::test_bool(truncated)])
+        };
+        assert_eq!(validity(&zip_file.path())?, expected(true, false));
+        assert_eq!(
+            validity(&truncated.to_string_lossy())?,
+            expected(false, true)
+        );
+        assert_eq!(
+            validity(&not_zip.to_string_lossy())?,
+            expected(false, false)
+        );
+        let res = make_plugin()?.eval(&format!("unzip -l {}", truncated.display()));
+        fs::remove_file(truncated)?;
+        fs::remove_file(not_zip)?;
+
+        assert!(res.unwrap_err().to_string().contains("looks truncated"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json() -> Result<()> {
+        let zip_file = TempZipFile::new(
+            &[
+                ("top.txt".to_string(), b"top".to_vec()),
+                ("dir/".to_string(), vec![]),
+                ("dir/sub/file.txt".to_string(), b"content".to_vec()),
+            ],
+            now(),
+        )?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "let manifest = unzip --to-json {}; \
+                 [$manifest.files $manifest.size $manifest.children.name \
+                  $manifest.children.0.children.0.children.0.name \
+                  $manifest.children.0.children.0.children.0.size]",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_int(2),
+                Value::test_filesize(10),
+                Value::test_list(vec![
+                    Value::test_string("dir"),
+                    Value::test_string("top.txt"),
+                ]),
+                Value::test_string("file.txt"),
+                Value::test_filesize(7),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_nested() -> Result<()> {
+        let zip_file = TempZipFile::new(
+            &[
+                ("top.txt".to_string(), b"top".to_vec()),
+                ("dir/".to_string(), vec![]),
+                ("dir/sub/file.txt".to_string(), b"content".to_vec()),
+            ],
+            now(),
+        )?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "let tree = unzip list --nested {}; \
+                 [$tree.name $tree.type $tree.0.children.0.children.0.name \
+                  $tree.0.children.0.children.0.size]",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        let top = make_plugin()?
+            .eval(&format!("(unzip list --nested {}).1", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_list(vec![
+                    Value::test_string("dir"),
+                    Value::test_string("top.txt"),
+                ]),
+                Value::test_list(vec![Value::test_string("dir"), Value::test_string("file")]),
+                Value::test_string("file.txt"),
+                Value::test_filesize(7),
+            ])
+        );
+        assert_eq!(
+            top.as_record()?.columns().collect::<Vec<_>>(),
+            ["name", "type", "size", "modified"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ext_filter() -> Result<()> {
+        let files = vec![
+            ("docs/".to_string(), vec![]),
+            ("docs/README.MD".to_string(), b"readme".to_vec()),
+            ("docs/notes.txt".to_string(), b"notes".to_vec()),
+            ("src/lib.rs".to_string(), b"code".to_vec()),
+            ("LICENSE".to_string(), b"license".to_vec()),
+            (".md".to_string(), b"hidden".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let listed = make_plugin()?
+            .eval(&format!(
+                "(unzip -l --ext [.md TXT] {}).name",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --ext md,txt {}", zip_file.path()))?;
+
+        assert_eq!(
+            listed,
+            Value::test_list(vec![
+                Value::test_string("docs/README.MD"),
+                Value::test_string("docs/notes.txt"),
+            ])
+        );
+        check_extracted_files(&files[1..3], current_dir.path());
+        assert!(!current_dir.path().join("src").exists());
+        assert!(!current_dir.path().join("LICENSE").exists());
+        assert!(!current_dir.path().join(".md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_closure() -> Result<()> {
+        let files = vec![
+            ("docs/".to_string(), vec![]),
+            ("docs/big.txt".to_string(), vec![b'a'; 100]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let mut archive = ZipArchive::new(File::open(zip_file.path())?)?;
+
+        // PluginTest can't evaluate closures, so check what they get and what is made of
+        // their results
+        let mut entry = |i| -> Result<Value> {
+            let file = archive.by_index(i)?;
+            let record = filter_entry(file.name(), &file, Span::test_data());
+            Ok(Value::test_list(vec![
+                record.get_data_by_key("size").unwrap(),
+                record.get_data_by_key("is_dir").unwrap(),
+            ]))
+        };
+        assert_eq!(
+            entry(1)?,
+            Value::test_list(vec![Value::test_filesize(100), Value::test_bool(false)])
+        );
+        assert_eq!(
+            entry(0)?,
+            Value::test_list(vec![Value::test_filesize(0), Value::test_bool(true)])
+        );
+
+        let mut log = Log {
+            quiet: true,
+            ..Default::default()
+        };
+        assert!(filter_selects("a", Ok(Value::test_bool(true)), &mut log));
+        assert!(!filter_selects("b", Ok(Value::test_bool(false)), &mut log));
+        assert!(!filter_selects("c", Ok(Value::test_int(1)), &mut log));
+        assert!(!filter_selects(
+            "d",
+            Err(ShellError::NushellFailed {
+                msg: "failed".to_string()
+            }),
+            &mut log
+        ));
+        assert_eq!(
+            log.warnings[0],
+            "Skipping c: --filter returned int instead of a bool"
+        );
+        assert_eq!(log.warnings.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_range() -> Result<()> {
+        let files = vec![
+            ("small.txt".to_string(), b"a".to_vec()),
+            ("medium.txt".to_string(), vec![b'b'; 100]),
+            ("large.txt".to_string(), vec![b'c'; 10000]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+        let output = plugin
+            .eval(&format!(
+                "(unzip -l --min-size 10b --max-size-entry 1kb {}).name",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_string("medium.txt")])
+        );
+
+        plugin.eval(&format!("unzip --min-size 1kb {}", zip_file.path()))?;
+        check_extracted_files(&files[2..], current_dir.path());
+        assert!(!current_dir.path().join("small.txt").exists());
+        assert!(!current_dir.path().join("medium.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_text() -> Result<()> {
+        let files = vec![
+            ("unix.txt".to_string(), b"a\nb\n".to_vec()),
+            ("dos.txt".to_string(), b"a\r\nb\r\n".to_vec()),
+            ("mixed.txt".to_string(), b"a\r\nb\n".to_vec()),
+            ("data.bin".to_string(), b"a\0\n".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "(unzip --verify-text {}).line_ending",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("lf"),
+                Value::test_string("crlf"),
+                Value::test_string("mixed"),
+                Value::test_string("binary"),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_tar() -> Result<()> {
+        let files = vec![
+            ("dir/".to_string(), vec![]),
+            ("dir/file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let tar_entries = |reader: &mut dyn Read| -> Result<Vec<(String, Vec<u8>)>> {
+            let mut entries = Vec::new();
+            for entry in tar::Archive::new(reader).entries()? {
+                let mut entry = entry?;
+                let mut name = entry.path()?.to_string_lossy().into_owned();
+                if entry.header().entry_type().is_dir() {
+                    name.push('/');
+                }
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                entries.push((name, contents));
+            }
+            Ok(entries)
+        };
+
+        plugin.eval(&format!("unzip --to-tar a.tar {}", zip_file.path()))?;
+        let mut tar_file = File::open(current_dir.path().join("a.tar"))?;
+        assert_eq!(tar_entries(&mut tar_file)?, files);
+
+        plugin.eval(&format!(
+            "unzip --to-tar-gz a.tar.gz --level 9 {}",
+            zip_file.path()
+        ))?;
+        let mut decoder =
+            flate2::read::GzDecoder::new(File::open(current_dir.path().join("a.tar.gz"))?);
+        assert_eq!(tar_entries(&mut decoder)?, files);
+
+        plugin.eval(&format!("unzip --to-tar-zst a.tar.zst {}", zip_file.path()))?;
+        let mut decoder = zstd::Decoder::new(File::open(current_dir.path().join("a.tar.zst"))?)?;
+        assert_eq!(tar_entries(&mut decoder)?, files);
+
+        let res = plugin.eval(&format!(
+            "unzip --to-tar-gz b.tar.gz --level 10 {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_to_value() -> Result<()> {
+        let files = vec![
+            ("config/".to_string(), vec![]),
+            ("config/app.toml".to_string(), b"name = \"app\"".to_vec()),
+            ("logo.bin".to_string(), vec![0xff, 0xfe]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!("unzip --extract-to-value {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_record(Record::from_iter([
+                (
+                    "config/app.toml".to_string(),
+                    Value::test_string("name = \"app\""),
+                ),
+                ("logo.bin".to_string(), Value::test_binary(vec![0xff, 0xfe])),
+            ]))
+        );
+
+        let result = make_plugin()?.eval(&format!(
+            "unzip --extract-to-value --max-size 10b {}",
+            zip_file.path()
+        ));
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_path_style() -> Result<()> {
+        let files = vec![("dir/sub/file.txt".to_string(), b"content".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "(unzip -l --path-style windows {}).name",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_string("dir\\sub\\file.txt")])
+        );
+
+        let res = make_plugin()?.eval(&format!("unzip -l --path-style dos {}", zip_file.path()));
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_subcommand() -> Result<()> {
+        let files = vec![
+            ("b.txt".to_string(), b"b".to_vec()),
+            ("a.txt".to_string(), b"a".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!("unzip list --sorted --names {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("a.txt"),
+                Value::test_string("b.txt")
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_sorted() -> Result<()> {
+        let files = vec![
+            ("b.txt".to_string(), b"b".to_vec()),
+            ("c/".to_string(), vec![]),
+            ("a.txt".to_string(), b"a".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let mut plugin = make_plugin()?;
+
+        let output = plugin
+            .eval(&format!("unzip --names {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("b.txt"),
+                Value::test_string("c/"),
+                Value::test_string("a.txt"),
+            ])
+        );
+
+        let output = plugin
+            .eval(&format!("(unzip -l --sorted {}).name", zip_file.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_string("a.txt"),
+                Value::test_string("b.txt"),
+                Value::test_string("c/"),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_atomic() -> Result<()> {
+        let files = vec![
+            ("small.txt".to_string(), b"small".to_vec()),
+            ("big.txt".to_string(), vec![b'a'; 5000]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let res = plugin.eval(&format!(
+            "unzip --atomic --max-size 2kb {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+        // only the complete file is left, without temporary files
+        let names: Vec<_> = fs::read_dir(current_dir.path())?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<std::io::Result<_>>()?;
+        assert_eq!(names, vec!["small.txt"]);
+
+        plugin.eval(&format!("unzip --atomic -f {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_keep_root() -> Result<()> {
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let flat = vec![
+            ("a.txt".to_string(), b"a".to_vec()),
+            ("sub/b.txt".to_string(), b"b".to_vec()),
+        ];
+        let flat_zip = TempZipFile::new(&flat, now())?;
+        plugin.eval(&format!("unzip --keep-root {}", flat_zip.path()))?;
+        let stem = Path::new(&flat_zip.path())
+            .file_stem()
+            .unwrap()
+            .to_os_string();
+        check_extracted_files(&flat, &current_dir.path().join(stem));
+
+        let rooted = vec![
+            ("root/".to_string(), vec![]),
+            ("root/c.txt".to_string(), b"c".to_vec()),
+        ];
+        let rooted_zip = TempZipFile::new(&rooted, now())?;
+        plugin.eval(&format!("unzip --keep-root {}", rooted_zip.path()))?;
+        check_extracted_files(&rooted, current_dir.path());
+
+        assert!(has_single_root(["./top/", "top/x", "top/y/z"].into_iter()));
+        assert!(!has_single_root(["top/x", "other/y"].into_iter()));
+        assert!(!has_single_root(["file"].into_iter()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_list_of_archives() -> Result<()> {
+        let files_a = vec![("a.txt".to_string(), b"a".to_vec())];
+        let files_b = vec![("b.txt".to_string(), b"b".to_vec())];
+        let zip_a = TempZipFile::new(&files_a, now())?;
+        let zip_b = TempZipFile::new(&files_b, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let missing = current_dir.path().join("missing.zip");
+        let res = plugin.eval(&format!(
+            "unzip [{} {} {}]",
+            zip_a.path(),
+            missing.display(),
+            zip_b.path()
+        ));
+        assert!(res.is_err());
+
+        plugin.eval(&format!(
+            "unzip -f -q --skip-errors [{} {} {}]",
+            zip_a.path(),
+            missing.display(),
+            zip_b.path()
+        ))?;
+        check_extracted_files(&files_a, current_dir.path());
+        check_extracted_files(&files_b, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_unsafe_name() -> Result<()> {
+        let files = vec![
+            ("ok.txt".to_string(), b"ok".to_vec()),
+            ("a/../b.txt".to_string(), b"b".to_vec()),
+            ("/tmp/evil.txt".to_string(), b"evil".to_vec()),
+            // made on Windows, unsafe on every platform
+            ("..\\evil.txt".to_string(), b"evil".to_vec()),
+            ("dir\\ok.txt".to_string(), b"ok".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).unsafe_name", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_bool(false),
+                Value::test_bool(true),
+                Value::test_bool(true),
+                Value::test_bool(true),
+                Value::test_bool(false),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_raw_name() -> Result<()> {
+        let zip_file = TempZipFile::new(&[("cafX.txt".to_string(), b"cafe".to_vec())], now())?;
+
+        // turn the name into Latin-1, which isn't valid UTF-8
+        let mut bytes = fs::read(zip_file.path())?;
+        for i in 0..bytes.len() - 8 {
+            if &bytes[i..i + 8] == b"cafX.txt" {
+                bytes[i + 3] = 0xe9;
+            }
+        }
+        fs::write(zip_file.path(), bytes)?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).0.raw_name", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(output, Value::test_binary(b"caf\xe9.txt".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_custom_fields() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let mut options = zip::write::FullFileOptions::default();
+        options.add_extra_data(0xcafe, vec![0; 6].into_boxed_slice(), true)?;
+        zip.start_file("file1.txt", options)?;
+        zip.write_all(b"content1")?;
+        zip.finish()?;
+
+        let output = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).0.custom_fields", path.display()))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_record(Record::from_iter([
+                ("tag".to_string(), Value::test_string("0xcafe")),
+                ("len".to_string(), Value::test_int(6)),
+            ]))])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_utf8_name() -> Result<()> {
+        // a non-ASCII name gets the UTF-8 flag, then break its encoding
+        let zip_file = TempZipFile::new(&[("caf\u{ff}.txt".to_string(), b"cafe".to_vec())], now())?;
+        let mut bytes = fs::read(zip_file.path())?;
+        for i in 0..bytes.len() - 5 {
+            if &bytes[i..i + 5] == b"caf\xc3\xbf" {
+                bytes[i + 3] = 0xff;
+            }
+        }
+        fs::write(zip_file.path(), bytes)?;
+        let current_dir = TempDir::new()?;
+
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "unzip {0}; (unzip -l {0}).0.name",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        // invalid sequences are replaced the same way in the name and the unzipped path
+        let name = "caf\u{fffd}\u{fffd}.txt";
+        assert_eq!(output, Value::test_string(name));
+        check_extracted_files(&[(name.to_string(), b"cafe".to_vec())], current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unzip_owner_map() -> Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let current_dir = TempDir::new()?;
+        let metadata = fs::metadata(current_dir.path())?;
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+
+        // an Info-ZIP Unix field with uid and gid 4242, the writer refuses its reserved header
+        // id, so use a placeholder and patch it
+        let mut field = vec![1, 4];
+        field.extend_from_slice(&4242u32.to_le_bytes());
+        field.push(4);
+        field.extend_from_slice(&4242u32.to_le_bytes());
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let mut options = zip::write::FullFileOptions::default();
+        options.add_extra_data(0xcafe, field.into_boxed_slice(), false)?;
+        zip.start_file("file1.txt", options)?;
+        zip.write_all(b"content1")?;
+        zip.finish()?;
+        let mut bytes = fs::read(&path)?;
+        let placeholder = [0xfe, 0xca, 11, 0];
+        for i in 0..bytes.len() - 4 {
+            if bytes[i..i + 4] == placeholder {
+                bytes[i..i + 2].copy_from_slice(&0x7875u16.to_le_bytes());
+            }
+        }
+        fs::write(&path, bytes)?;
+
+        // map the stored owner to the current one, which needs no privileges, the first rule
+        // doesn't apply
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --preserve-ownership --owner-map ['1:1=0:0' '4242:4242={uid}:{gid}'] {}",
+            path.display()
+        ))?;
+        fs::remove_file(path)?;
+
+        let metadata = fs::metadata(current_dir.path().join("file1.txt"))?;
+        assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+
+        assert_eq!(
+            parse_owner_rules(Value::test_string("1000:100=0:0"), Span::test_data()).unwrap(),
+            vec![OwnerRule {
+                from: (1000, 100),
+                to: (0, 0)
+            }]
+        );
+        assert!(parse_owner_rules(Value::test_string("1000=0"), Span::test_data()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_offset() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        // an image with a header before the archive
+        let mut image = vec![b'x'; 1000];
+        image.extend(fs::read(zip_file.path())?);
+        let path = testfile::generate_name();
+        fs::write(&path, &image)?;
+        let current_dir = TempDir::new()?;
+
+        let names = make_plugin()?
+            .eval(&format!(
+                "(unzip -l --offset 1000b {}).name",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --offset 1000b {}", path.display()))?;
+        let res = make_plugin()?.eval(&format!("unzip -l --offset 1mb {}", path.display()));
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            names,
+            Value::test_list(vec![Value::test_string("file1.txt")])
+        );
+        check_extracted_files(&files, current_dir.path());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Error opening ZIP file"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_current_dir_fallback() -> Result<()> {
+        let engine_error = ShellError::GenericError {
+            error: "no current directory".into(),
+            msg: "".into(),
+            span: None,
+            help: None,
+            inner: vec![],
+        };
+
+        assert_eq!(
+            current_dir(Ok("/engine/dir".to_string()))?,
+            PathBuf::from("/engine/dir")
+        );
+        assert_eq!(current_dir(Err(engine_error))?, std::env::current_dir()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_parse() -> Result<()> {
+        let files = vec![(
+            "data.nuon".to_string(),
+            b"[{name: a, size: 1}, {name: b, size: 2}]".to_vec(),
+        )];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let sizes = make_plugin()?
+            .eval(&format!(
+                "(unzip --entry data.nuon --parse nuon {}).size",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        let res = make_plugin()?.eval(&format!(
+            "unzip --entry data.nuon --parse xml {}",
+            zip_file.path()
+        ));
+
+        assert_eq!(
+            sizes,
+            Value::test_list(vec![Value::test_int(1), Value::test_int(2)])
+        );
+        assert!(res.unwrap_err().to_string().contains("Unknown format xml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_sort_dirs_first() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("b.txt", options)?;
+        zip.add_directory("z/", options)?;
+        zip.start_file("a.txt", options)?;
+        zip.add_directory("c/", options)?;
+        zip.finish()?;
+
+        let names = make_plugin()?
+            .eval(&format!(
+                "(unzip -l --sort-dirs-first {}).name",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            names,
+            Value::test_list(
+                ["c/", "z/", "a.txt", "b.txt"]
+                    .into_iter()
+                    .map(Value::test_string)
+                    .collect()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_format() -> Result<()> {
+        let files = vec![
+            ("a,b.txt".to_string(), b"abc".to_vec()),
+            ("dir/\"quoted\".txt".to_string(), vec![b'a'; 2048]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let lines = |format: &str| -> Result<Vec<String>> {
+            let text = make_plugin()?
+                .eval(&format!("unzip -l --format {format} {}", zip_file.path()))?
+                .into_value(Span::test_data())?
+                .into_string()?;
+            Ok(text.lines().map(|line| line.to_string()).collect())
+        };
+        let csv = lines("csv")?;
+        assert_eq!(csv.len(), 3);
+        assert_eq!(csv[0], "name,size,modified,depth");
+        assert!(csv[1].starts_with("\"a,b.txt\",3,"), "{}", csv[1]);
+        assert!(
+            csv[2].starts_with("\"dir/\"\"quoted\"\".txt\",2048,"),
+            "{}",
+            csv[2]
+        );
+        assert!(csv[2].ends_with(",1"), "{}", csv[2]);
+
+        let tsv = lines("tsv")?;
+        assert_eq!(tsv[0], "name\tsize\tmodified\tdepth");
+        assert!(tsv[1].starts_with("a,b.txt\t3\t"), "{}", tsv[1]);
+
+        let res = make_plugin()?.eval(&format!("unzip -l --format xml {}", zip_file.path()));
+        assert!(res.unwrap_err().to_string().contains("Unknown format xml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let hex: String = fs::read(zip_file.path())?
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let current_dir = TempDir::new()?;
+
+        let names = make_plugin()?
+            .eval(&format!(
+                "let data = 0x[{hex}]; (unzip -l --data $data).name"
+            ))?
+            .into_value(Span::test_data())?;
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("let data = 0x[{hex}]; unzip --data $data"))?;
+        let both = make_plugin()?.eval(&format!("unzip -l --data 0x[{hex}] {}", zip_file.path()));
+        let neither = make_plugin()?.eval("unzip -l");
+
+        assert_eq!(
+            names,
+            Value::test_list(vec![Value::test_string("file1.txt")])
+        );
+        check_extracted_files(&files, current_dir.path());
+        assert!(both
+            .unwrap_err()
+            .to_string()
+            .contains("Conflicting arguments"));
+        assert!(neither.unwrap_err().to_string().contains("Missing archive"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_if_empty() -> Result<()> {
+        let empty = testfile::generate_name();
+        zip::ZipWriter::new(File::create(&empty)?).finish()?;
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let eval = |command: String| {
+            make_plugin_with_pwd(current_dir.path())
+                .unwrap()
+                .eval(&command)
+                .map_err(|e| e.to_string())
+        };
+        // empty is fine by default
+        assert!(eval(format!("unzip {}", empty.display())).is_ok());
+        assert!(eval(format!("unzip -l --regex md$ {}", zip_file.path())).is_ok());
+
+        for command in [
+            format!("unzip --fail-if-empty {}", empty.display()),
+            format!("unzip list --fail-if-empty {}", empty.display()),
+            format!("unzip --fail-if-empty --regex md$ {}", zip_file.path()),
+            format!("unzip -l --fail-if-empty --regex md$ {}", zip_file.path()),
+        ] {
+            let error = eval(command).unwrap_err();
+            assert!(error.contains("No entries to"), "{error}");
+        }
+        fs::remove_file(empty)?;
+
+        assert!(eval(format!("unzip --fail-if-empty {}", zip_file.path())).is_ok());
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entries() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"content2".to_vec()),
+            ("file3.txt".to_string(), b"content3".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        let res = plugin.eval(&format!("unzip --max-entries 2 {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .contains("Archive has 3 entries, more than the limit of 2"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
+
+        assert!(plugin
+            .eval(&format!("unzip -l --max-entries 2 {}", zip_file.path()))
+            .is_err());
+
+        plugin.eval(&format!("unzip --max-entries 3 {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unzip_chmod() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let files = vec![
+            ("dir/".to_string(), vec![]),
+            ("dir/file1.txt".to_string(), b"content1".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        plugin.eval(&format!(
+            "unzip --chmod 0600 --chmod-dirs 0750 {}",
+            zip_file.path()
+        ))?;
+        let mode = |path: &str| -> Result<u32> {
+            Ok(fs::metadata(current_dir.path().join(path))?
+                .permissions()
+                .mode()
+                & 0o7777)
+        };
+        assert_eq!(mode("dir")?, 0o750);
+        assert_eq!(mode("dir/file1.txt")?, 0o600);
+
+        let res = plugin.eval(&format!("unzip -f --chmod 0999 {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("Invalid mode 0999"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_resume() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("file2.txt".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        // pretend an earlier run was interrupted after the first entry
+        let resume_file = current_dir
+            .path()
+            .join(resume_file_name(Path::new(&zip_file.path())));
+        fs::write(&resume_file, "0\n")?;
+
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --resume --delete-archive {}",
+            zip_file.path()
+        ))?;
+        assert!(!current_dir.path().join("file1.txt").exists());
+        check_extracted_files(&files[1..], current_dir.path());
+        assert!(!resume_file.exists());
+        // the entry unzipped by the earlier run doesn't count as skipped
+        assert!(!Path::new(&zip_file.path()).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_manifest_in() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("dir/file2.txt".to_string(), b"content2".to_vec()),
+            ("file3.txt".to_string(), b"content3".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        fs::write(
+            current_dir.path().join("wanted.nuon"),
+            r#"["file1.txt", "dir/file2.txt"]"#,
+        )?;
+        fs::write(
+            current_dir.path().join("missing.txt"),
+            "file1.txt\nfile4.txt\n",
+        )?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+        plugin.eval(&format!(
+            "unzip --manifest-in wanted.nuon {}",
+            zip_file.path()
+        ))?;
+        check_extracted_files(&files[..2], current_dir.path());
+        assert!(!current_dir.path().join("file3.txt").exists());
+
+        let res = plugin.eval(&format!(
+            "unzip -f --manifest-in missing.txt {}",
+            zip_file.path()
+        ));
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_entries_from_stdin() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("dir/file2.txt".to_string(), b"content2".to_vec()),
+            ("file3.txt".to_string(), b"content3".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+        plugin.eval(&format!(
+            "[file1.txt dir/file2.txt] | unzip --entries-from-stdin {}",
+            zip_file.path()
+        ))?;
+        check_extracted_files(&files[..2], current_dir.path());
+        assert!(!current_dir.path().join("file3.txt").exists());
+
+        let res = plugin.eval(&format!(
+            "[file1.txt file4.txt] | unzip -f --entries-from-stdin {}",
+            zip_file.path()
+        ));
+        assert!(res.unwrap_err().to_string().contains("not found"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_throughput() {
+        assert_eq!(throughput(5_000_000, Duration::from_secs(2)), "2.5 MB/s");
+        assert_eq!(throughput(100, Duration::ZERO), "too fast to measure");
+    }
+
+    #[test]
+    fn test_skip_hidden() -> Result<()> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("shown.txt", options)?;
+        zip.write_all(b"shown")?;
+        zip.start_file("desktop.ini", options)?;
+        zip.write_all(b"hidden")?;
+        zip.finish()?;
+        // set the hidden and system DOS attributes of desktop.ini in the central directory
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 46 {
+            if bytes[i..i + 4] == [0x50, 0x4b, 0x01, 0x02]
+                && bytes[i + 46..].starts_with(b"desktop")
+            {
+                bytes[i + 38] |= 0x06;
+            }
+        }
+        fs::write(&path, bytes)?;
+        let current_dir = TempDir::new()?;
+
+        let attributes = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).attributes", path.display()))?
+            .into_value(Span::test_data())?;
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --skip-hidden {}", path.display()))?;
+        fs::remove_file(path)?;
+
+        assert_eq!(
+            attributes,
+            Value::test_list(vec![
+                Value::test_list(vec![]),
+                Value::test_list(vec![
+                    Value::test_string("hidden"),
+                    Value::test_string("system")
+                ]),
+            ])
+        );
+        assert!(current_dir.path().join("shown.txt").exists());
+        assert!(!current_dir.path().join("desktop.ini").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_into() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("dir/file2.txt".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        let target = current_dir.path().join("target");
+        fs::create_dir(&target)?;
+        fs::write(target.join("file1.txt"), "old")?;
+        fs::write(target.join("keep.txt"), "kept")?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        // a conflict leaves the target as it was
+        let res = plugin.eval(&format!("unzip --into target {}", zip_file.path()));
+        assert!(res.unwrap_err().to_string().contains("Files already exist"));
+        assert_eq!(fs::read_to_string(target.join("file1.txt"))?, "old");
+        assert!(!target.join("dir").exists());
+
+        plugin.eval(&format!("unzip -f --into target {}", zip_file.path()))?;
+        check_extracted_files(&files, &target);
+        assert_eq!(fs::read_to_string(target.join("keep.txt"))?, "kept");
+        // no staging directory is left behind
+        assert_eq!(fs::read_dir(current_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_on_conflict() -> Result<()> {
+        let files = vec![("a.txt".to_string(), b"new".to_vec())];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+        fs::write(current_dir.path().join("a.txt"), "old")?;
+        fs::write(current_dir.path().join("a (1).txt"), "old")?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        // every run takes the next free number
+        plugin.eval(&format!("unzip --rename-on-conflict {}", zip_file.path()))?;
+        plugin.eval(&format!("unzip --rename-on-conflict {}", zip_file.path()))?;
+        plugin.eval(&format!(
+            "unzip --conflict-format '{{stem}}.{{n}}{{ext}}' {}",
+            zip_file.path()
+        ))?;
+        let read = |name: &str| fs::read_to_string(current_dir.path().join(name));
+        assert_eq!(read("a.txt")?, "old");
+        assert_eq!(read("a (1).txt")?, "old");
+        assert_eq!(read("a (2).txt")?, "new");
+        assert_eq!(read("a (3).txt")?, "new");
+        assert_eq!(read("a.1.txt")?, "new");
+
+        for (format, error) in [
+            ("{stem}{ext}", "Invalid conflict format"),
+            ("{stem}-{count}{ext}", "Unknown placeholder {count}"),
+            ("{n}/{stem}", "Invalid conflict format"),
+        ] {
+            let res = plugin.eval(&format!(
+                "unzip --conflict-format '{format}' {}",
+                zip_file.path()
+            ));
+            assert!(res.unwrap_err().to_string().contains(error), "{format}");
+        }
+
+        Ok(())
+    }
+
+    /// Unzip entries with both separators, returning the target directory
+    fn unzip_mixed_separators() -> Result<TempDir> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        for name in [
+            "dir\\file1.txt",
+            "dir/file2.txt",
+            "other\\sub/file3.txt",
+            "..\\evil.txt",
+        ] {
+            zip.start_file(name, options)?;
+            zip.write_all(name.as_bytes())?;
+        }
+        zip.finish()?;
+        let current_dir = TempDir::new()?;
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!("unzip -q {}", path.display()))?;
+        fs::remove_file(path)?;
+        Ok(current_dir)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unzip_backslashes_unix() -> Result<()> {
+        let current_dir = unzip_mixed_separators()?;
+        let mut files = Vec::new();
+        walk_files(current_dir.path(), &mut files)?;
+        files.sort();
+
+        assert_eq!(
+            files,
+            ["dir/file1.txt", "dir/file2.txt", "other/sub/file3.txt"]
+                .map(|file| current_dir.path().join(file))
+        );
+        assert!(!current_dir.path().join("dir\\file1.txt").exists());
+        assert!(!current_dir.path().join("..\\evil.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn test_unzip_backslashes_windows() -> Result<()> {
+        let current_dir = unzip_mixed_separators()?;
+        let mut files = Vec::new();
+        walk_files(current_dir.path(), &mut files)?;
+        files.sort();
+
+        assert_eq!(
+            files,
+            ["dir\\file1.txt", "dir\\file2.txt", "other\\sub\\file3.txt"]
+                .map(|file| current_dir.path().join(file))
+        );
+        assert!(!current_dir
+            .path()
+            .parent()
+            .unwrap()
+            .join("evil.txt")
+            .exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_by_size() -> Result<()> {
+        let files = vec![
+            ("small.txt".to_string(), vec![b'a'; 10]),
+            ("dir/".to_string(), vec![]),
+            ("dir/large.txt".to_string(), vec![b'a'; 1000]),
+            ("medium.txt".to_string(), vec![b'a'; 100]),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let current_dir = TempDir::new()?;
+
+        let report = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --report-by-size {}", zip_file.path()))?
+            .into_value(Span::test_data())?;
+
+        let row = |path: &str, size| {
+            Value::test_record(Record::from_iter([
+                (
+                    "path".to_string(),
+                    Value::test_string(current_dir.path().join(path).to_string_lossy()),
+                ),
+                ("size".to_string(), Value::test_filesize(size)),
+            ]))
+        };
+        assert_eq!(
+            report,
+            Value::test_list(vec![
+                row("dir/large.txt", 1000),
+                row("medium.txt", 100),
+                row("small.txt", 10),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_tmp() -> Result<()> {
+        let files = vec![
+            ("file1.txt".to_string(), b"content1".to_vec()),
+            ("dir/file2.txt".to_string(), b"content2".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        let mut plugin = make_plugin()?;
+        let dir = PathBuf::from(
+            plugin
+                .eval(&format!("unzip --tmp {}", zip_file.path()))?
+                .into_value(Span::test_data())?
+                .into_string()?,
+        );
+        let other = PathBuf::from(
+            plugin
+                .eval(&format!("unzip --tmp {}", zip_file.path()))?
+                .into_value(Span::test_data())?
+                .into_string()?,
+        );
+        check_extracted_files(&files, &dir);
+        let expected_root = if cfg!(target_os = "linux") && Path::new("/dev/shm").is_dir() {
+            PathBuf::from("/dev/shm")
+        } else {
+            std::env::temp_dir()
+        };
+        let in_root = dir.parent() == Some(expected_root.as_path());
+        // cleaning up is left to the caller
+        fs::remove_dir_all(&dir)?;
+        fs::remove_dir_all(&other)?;
+
+        assert_ne!(dir, other);
+        assert!(in_root, "{}", dir.display());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_archive() -> Result<()> {
+        let files = vec![("file1.txt".to_string(), b"content1".to_vec())];
+        let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+
+        // a failure keeps the archive
+        let zip_file = TempZipFile::new(&files, now())?;
+        fs::write(current_dir.path().join("file1.txt"), "existing")?;
+        let res = plugin.eval(&format!("unzip --delete-archive {}", zip_file.path()));
+        assert!(res.is_err());
+        assert!(Path::new(&zip_file.path()).exists());
+
+        plugin.eval(&format!("unzip -f --delete-archive {}", zip_file.path()))?;
+        check_extracted_files(&files, current_dir.path());
+        assert!(!Path::new(&zip_file.path()).exists());
+
+        // so do skipped entries
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        zip.start_file("../evil.txt", zip::write::SimpleFileOptions::default())?;
+        zip.finish()?;
+        plugin.eval(&format!("unzip -q --delete-archive {}", path.display()))?;
+        assert!(path.exists());
+        fs::remove_file(path)?;
+
+        let res = plugin.eval("unzip --delete-archive --data 0x[00]");
+        assert!(res.unwrap_err().to_string().contains("Conflicting flags"));
+        let res = plugin.eval("unzip --delete-archive --from-dir .");
+        assert!(res.unwrap_err().to_string().contains("Conflicting flags"));
+
+        Ok(())
     }
 
-    impl TempZipFile {
-        fn new(files: &[(String, Vec<u8>)], modified: DateTime<Local>) -> Result<Self> {
-            let path = testfile::generate_name();
-            let file = File::create(&path)?;
-            let modified = modified.naive_local();
-
-            let mut zip = zip::ZipWriter::new(file);
-            for (name, content) in files {
-                zip.start_file(
-                    name,
-                    zip::write::SimpleFileOptions::default()
-                        .compression_method(zip::CompressionMethod::Deflated)
-                        .last_modified_time(modified.try_into()?),
-                )?;
-                zip.write_all(content)?;
+    #[test]
+    fn test_since_archive() -> Result<()> {
+        let release = |path: &PathBuf, files: &[(&str, &str)]| -> Result<()> {
+            let mut zip = zip::ZipWriter::new(File::create(path)?);
+            for (name, contents) in files {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default())?;
+                zip.write_all(contents.as_bytes())?;
             }
             zip.finish()?;
-            Ok(Self { _path: path })
-        }
+            Ok(())
+        };
+        let old = testfile::generate_name();
+        let new = testfile::generate_name();
+        release(&old, &[("same.txt", "same"), ("changed.txt", "v1")])?;
+        release(
+            &new,
+            &[
+                ("same.txt", "same"),
+                ("changed.txt", "v2"),
+                ("added.txt", "new"),
+            ],
+        )?;
+        let current_dir = TempDir::new()?;
 
-        fn path(&self) -> String {
-            self._path.as_path().to_string_lossy().to_string()
-        }
-    }
+        let listed = make_plugin()?
+            .eval(&format!(
+                "let listed = unzip list --since-archive {} {}; [$listed.name $listed.change]",
+                old.display(),
+                new.display()
+            ))?
+            .into_value(Span::test_data())?;
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --since-archive {} {}",
+            old.display(),
+            new.display()
+        ))?;
+        fs::remove_file(old)?;
+        fs::remove_file(new)?;
 
-    impl Drop for TempZipFile {
-        fn drop(&mut self) {
-            let _ = std::fs::remove_file(&self._path);
-        }
-    }
+        let strings =
+            |strings: [&str; 2]| Value::test_list(strings.map(Value::test_string).to_vec());
+        assert_eq!(
+            listed,
+            Value::test_list(vec![
+                strings(["changed.txt", "added.txt"]),
+                strings(["changed", "added"]),
+            ])
+        );
+        let mut names: Vec<_> = fs::read_dir(current_dir.path())?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        assert_eq!(names, ["added.txt", "changed.txt"]);
+        assert_eq!(
+            fs::read_to_string(current_dir.path().join("changed.txt"))?,
+            "v2"
+        );
 
-    struct TempDir {
-        _path: PathBuf,
+        Ok(())
     }
 
-    impl TempDir {
-        fn new() -> Result<Self> {
-            let path = testfile::generate_name();
-            std::fs::create_dir_all(&path)?;
-            Ok(Self { _path: path })
-        }
+    #[test]
+    #[cfg(unix)]
+    fn test_out_encoding() -> Result<()> {
+        use std::os::unix::ffi::OsStrExt;
 
-        fn path(&self) -> &Path {
-            self._path.as_path()
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        for name in ["caf\u{e9}.txt", "\u{65e5}\u{672c}.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"text")?;
         }
-    }
+        zip.finish()?;
+        let current_dir = TempDir::new()?;
 
-    impl Drop for TempDir {
-        fn drop(&mut self) {
-            std::fs::remove_dir_all(&self._path).unwrap();
-        }
-    }
+        let error = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --out-encoding latin1 {}", path.display()))
+            .err()
+            .map(|e| e.to_string());
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip -q --out-encoding latin1 --unmappable skip {}",
+            path.display()
+        ))?;
+        fs::remove_file(path)?;
 
-    fn make_list_result(files: &[(String, Vec<u8>)], modified: DateTime<Local>) -> Value {
-        let items: Vec<_> = files
-            .iter()
-            .map(|(name, contents)| {
-                let item = vec![
-                    ("name".to_string(), Value::string(name, Span::test_data())),
-                    (
-                        "size".to_string(),
-                        Value::filesize(contents.len() as i64, Span::test_data()),
-                    ),
-                    (
-                        "modified".to_string(),
-                        Value::date(modified.into(), Span::test_data()),
-                    ),
-                ];
-                Record::from_iter(item).into_value(Span::test_data())
-            })
-            .collect();
-        Value::list(items, Span::test_data())
+        assert!(error.is_some_and(|e| e.contains("can't be written in windows-1252")));
+        let names: Vec<_> = fs::read_dir(current_dir.path())?
+            .map(|entry| Ok(entry?.file_name().as_bytes().to_vec()))
+            .collect::<Result<_>>()?;
+        assert_eq!(names, [b"caf\xe9.txt".to_vec()]);
+
+        Ok(())
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_not_exists() -> Result<()> {
-        let mut plugin = make_plugin()?;
+    fn test_out_encoding_from_fifo() -> Result<()> {
+        let files = vec![
+            ("caf\u{e9}.txt".to_string(), b"text".to_vec()),
+            ("\u{65e5}\u{672c}.txt".to_string(), b"text".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(&files, now())?;
+        let (_dir, fifo) = make_fifo(fs::read(zip_file.path())?)?;
+        let current_dir = TempDir::new()?;
 
-        let not_exists_file = testfile::generate_name();
-        let res = plugin.eval(&format!("unzip {}", not_exists_file.to_string_lossy()));
+        let res = make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip --out-encoding latin1 {}",
+            fifo.to_string_lossy()
+        ));
 
-        assert!(res.is_err());
         assert!(res
-            .err()
-            .unwrap()
+            .unwrap_err()
             .to_string()
-            .contains("Error opening ZIP file"),);
+            .contains("can't be written in windows-1252"));
+        assert!(fs::read_dir(current_dir.path())?.next().is_none());
 
         Ok(())
     }
 
     #[test]
-    fn test_list_empty_zip() -> Result<()> {
-        let zip_file = TempZipFile::new(&[], now())?;
+    fn test_entry_info() -> Result<()> {
+        let zip_file = TempZipFile::new(
+            &[
+                ("docs/".to_string(), vec![]),
+                ("docs/readme.md".to_string(), b"hello".to_vec()),
+            ],
+            now(),
+        )?;
 
-        let output = make_plugin()?
-            .eval(&format!("unzip -l {}", zip_file.path()))?
+        let info = make_plugin()?
+            .eval(&format!(
+                "let info = unzip --entry-info docs/readme.md {}; \
+                 {{name: $info.name, size: $info.size, crc32: $info.crc32, encrypted: $info.encrypted}}",
+                zip_file.path()
+            ))?
             .into_value(Span::test_data())?;
+        let error = make_plugin()?
+            .eval(&format!(
+                "unzip --entry-info docs/readme.mb {}",
+                zip_file.path()
+            ))
+            .err()
+            .map(|e| format!("{e:?}"));
 
-        assert_eq!(output, Value::list(vec![], Span::test_data()));
+        assert_eq!(
+            info,
+            Value::test_record(Record::from_iter([
+                ("name".to_string(), Value::test_string("docs/readme.md")),
+                ("size".to_string(), Value::test_filesize(5)),
+                ("crc32".to_string(), Value::test_int(0x3610a686)),
+                ("encrypted".to_string(), Value::test_bool(false)),
+            ]))
+        );
+        assert!(error.is_some_and(|e| e.contains("Did you mean docs/readme.md?")));
 
         Ok(())
     }
 
     #[test]
-    fn test_list_simple_zip() -> Result<()> {
-        let files = vec![
-            ("file1.txt".to_string(), b"content1".to_vec()),
-            ("file2.txt".to_string(), b"hello content2".to_vec()),
-        ];
-        let modified = now();
-        let zip_file = TempZipFile::new(&files, modified)?;
+    fn test_unzip_over_archive() -> Result<()> {
+        let current_dir = TempDir::new()?;
+        let archive = current_dir.path().join("a.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive)?);
+        for name in ["a.zip", "b.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"not the archive")?;
+        }
+        zip.finish()?;
+        let original = fs::read(&archive)?;
 
-        let output = make_plugin()?
-            .eval(&format!("unzip -l {}", zip_file.path()))?
+        let report = make_plugin_with_pwd(current_dir.path())?
+            .eval("(unzip -q --force --report a.zip).skipped_unsafe")?
             .into_value(Span::test_data())?;
 
-        assert_eq!(output, make_list_result(&files, modified));
+        assert_eq!(report, Value::test_int(1));
+        assert_eq!(fs::read(&archive)?, original);
+        assert_eq!(
+            fs::read(current_dir.path().join("b.txt"))?,
+            b"not the archive"
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_unzip_empty_zip() -> Result<()> {
-        let zip_file = TempZipFile::new(&[], now())?;
+    fn test_unzip_from_dir() -> Result<()> {
+        let current_dir = TempDir::new()?;
+        let downloads = current_dir.path().join("downloads");
+        fs::create_dir_all(downloads.join("sub"))?;
+        for path in [downloads.join("a.zip"), downloads.join("sub/c.zip")] {
+            let mut zip = zip::ZipWriter::new(File::create(path)?);
+            zip.start_file("file.txt", zip::write::SimpleFileOptions::default())?;
+            zip.write_all(b"text")?;
+            zip.finish()?;
+        }
+        fs::write(downloads.join("b.zip"), b"not a zip")?;
+        fs::write(downloads.join("notes.txt"), b"not an archive")?;
+
+        let summary = make_plugin_with_pwd(current_dir.path())?
+            .eval(
+                "let summary = unzip -q --skip-errors --from-dir downloads; \
+                 [$summary.extracted $summary.errors]",
+            )?
+            .into_value(Span::test_data())?
+            .into_list()?;
+        let err = make_plugin_with_pwd(current_dir.path())?
+            .eval("unzip --from-dir downloads")
+            .is_err();
+        make_plugin_with_pwd(current_dir.path())?
+            .eval("unzip -q --force --skip-errors --recursive --from-dir downloads")?;
+
+        assert_eq!(
+            summary[0],
+            Value::test_list(vec![Value::test_int(1), Value::test_int(0)])
+        );
+        let errors = summary[1].as_list()?;
+        assert!(errors[0].is_nothing() && !errors[1].is_nothing());
+        assert!(err);
+        assert_eq!(fs::read(current_dir.path().join("a/file.txt"))?, b"text");
+        assert_eq!(
+            fs::read(current_dir.path().join("sub/c/file.txt"))?,
+            b"text"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_bom() -> Result<()> {
+        let files = [
+            ("utf8.txt", b"\xef\xbb\xbfhello".to_vec()),
+            ("utf16.txt", b"\xff\xfeh\0i\0".to_vec()),
+            ("utf32.txt", b"\xff\xfe\0\0h\0\0\0".to_vec()),
+            ("binary.bin", b"\xef\xbb\xbf\0\x01\x02".to_vec()),
+            ("plain.txt", b"hello".to_vec()),
+        ];
+        let zip_file = TempZipFile::new(
+            &files.clone().map(|(name, data)| (name.to_string(), data)),
+            now(),
+        )?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip --strip-bom {}", zip_file.path()))?;
+
+        let read = |name: &str| fs::read(current_dir.path().join(name)).unwrap();
+        assert_eq!(read("utf8.txt"), b"hello");
+        assert_eq!(read("utf16.txt"), b"h\0i\0");
+        assert_eq!(read("utf32.txt"), files[2].1);
+        assert_eq!(read("binary.bin"), files[3].1);
+        assert_eq!(read("plain.txt"), b"hello");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_list() -> Result<()> {
+        let saved = TempZipFile::new(
+            &[
+                ("a, b.txt".to_string(), b"same".to_vec()),
+                ("changed.txt".to_string(), b"v1".to_vec()),
+                ("removed.txt".to_string(), b"gone".to_vec()),
+            ],
+            now(),
+        )?;
+        let new = TempZipFile::new(
+            &[
+                ("a, b.txt".to_string(), b"same".to_vec()),
+                ("changed.txt".to_string(), b"v2".to_vec()),
+                ("added.txt".to_string(), b"new".to_vec()),
+            ],
+            now(),
+        )?;
         let current_dir = TempDir::new()?;
 
+        let crc = make_plugin()?
+            .eval(&format!(
+                "(unzip list --with-crc-hex {}).crc32.0",
+                saved.path()
+            ))?
+            .into_value(Span::test_data())?;
+        let csv = make_plugin()?
+            .eval(&format!(
+                "unzip -l --with-crc-hex --format csv {}",
+                saved.path()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::write(current_dir.path().join("list.csv"), csv.as_str()?)?;
         let output = make_plugin_with_pwd(current_dir.path())?
-            .eval(&format!("unzip {}", zip_file.path()))?
+            .eval(&format!(
+                "let rows = unzip --verify-list list.csv {}; [$rows.name $rows.status]",
+                new.path()
+            ))?
             .into_value(Span::test_data())?;
 
-        assert_eq!(output, Value::nothing(Span::test_data()));
-
-        assert!(fs::read_dir(current_dir.path()).unwrap().next().is_none());
+        assert_eq!(
+            crc,
+            Value::test_string(format!("{:08x}", crc32fast::hash(b"same")))
+        );
+        let strings =
+            |strings: [&str; 4]| Value::test_list(strings.map(Value::test_string).to_vec());
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                strings(["a, b.txt", "changed.txt", "added.txt", "removed.txt"]),
+                strings(["ok", "mismatch", "extra", "missing"]),
+            ])
+        );
 
         Ok(())
     }
 
-    fn check_extracted_files(files: &[(String, Vec<u8>)], directory: &Path) {
-        for (file_name, file_contents) in files {
-            let file_path = directory.join(file_name);
-            assert!(file_path.exists());
-            assert_eq!(
-                &fs::read(file_path).unwrap(),
-                file_contents,
-                "File contents differ for {}",
-                file_name
-            );
+    /// An archive with a `link` entry to `target`, marked as a reparse point
+    fn reparse_point_zip(target: &str) -> Result<PathBuf> {
+        let path = testfile::generate_name();
+        let mut zip = zip::ZipWriter::new(File::create(&path)?);
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("target.txt", options)?;
+        zip.write_all(b"target")?;
+        zip.start_file("link", options)?;
+        zip.write_all(target.as_bytes())?;
+        zip.finish()?;
+        // set FILE_ATTRIBUTE_REPARSE_POINT, 0x400, in the central directory
+        let mut bytes = fs::read(&path)?;
+        for i in 0..bytes.len() - 46 {
+            if bytes[i..i + 4] == [0x50, 0x4b, 0x01, 0x02] && bytes[i + 46..].starts_with(b"link") {
+                bytes[i + 39] |= 0x04;
+            }
         }
+        fs::write(&path, bytes)?;
+        Ok(path)
     }
 
     #[test]
-    fn test_unzip_simple_zip() -> Result<()> {
-        let files = vec![
-            ("file1.txt".to_string(), b"content1".to_vec()),
-            ("file2.txt".to_string(), b"hello content2".to_vec()),
-        ];
-        let modified = now();
-        let zip_file = TempZipFile::new(&files, modified)?;
+    fn test_reparse_point() -> Result<()> {
+        let path = reparse_point_zip("target.txt")?;
         let current_dir = TempDir::new()?;
 
-        let output = make_plugin_with_pwd(current_dir.path())?
-            .eval(&format!("unzip {}", zip_file.path()))?
+        let attributes = make_plugin()?
+            .eval(&format!("(unzip -l -v {}).attributes.1", path.display()))?
             .into_value(Span::test_data())?;
+        // by default reparse points are plain files with their target as content
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!("unzip {}", path.display()))?;
+        fs::remove_file(path)?;
 
-        assert_eq!(output, Value::nothing(Span::test_data()));
+        assert_eq!(
+            attributes,
+            Value::test_list(vec![Value::test_string("reparse_point")])
+        );
+        let link = current_dir.path().join("link");
+        assert!(!link.symlink_metadata()?.is_symlink());
+        assert_eq!(fs::read_to_string(link)?, "target.txt");
 
-        check_extracted_files(&files, current_dir.path());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn test_allow_reparse_elsewhere() -> Result<()> {
+        let path = reparse_point_zip("target.txt")?;
+        let current_dir = TempDir::new()?;
+
+        make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!("unzip -q --allow-reparse {}", path.display()))?;
+        fs::remove_file(path)?;
+
+        let link = current_dir.path().join("link");
+        assert!(!link.symlink_metadata()?.is_symlink());
+        assert_eq!(fs::read_to_string(link)?, "target.txt");
 
         Ok(())
     }
 
+    #[test]
+    #[cfg(windows)]
+    fn test_allow_reparse_windows() -> Result<()> {
+        // links out of the target directory are refused, whatever the privileges
+        let path = reparse_point_zip("..\\..\\evil.txt")?;
+        let current_dir = TempDir::new()?;
+
+        let report = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "(unzip -q --allow-reparse --report {}).skipped_unsafe",
+                path.display()
+            ))?
+            .into_value(Span::test_data())?;
+        fs::remove_file(path)?;
+
+        assert_eq!(report, Value::test_int(1));
+        assert!(current_dir.path().join("link").symlink_metadata().is_err());
+        assert!(current_dir.path().join("target.txt").exists());
+
+        Ok(())
+    }
 
     #[test]
-    fn test_unzip_with_folder() -> Result<()> {
+    fn test_unzip_spool() -> Result<()> {
         let files = vec![
             ("file1.txt".to_string(), b"content1".to_vec()),
-            ("a_dir/file2.txt".to_string(), b"hello content2".to_vec()),
+            ("dir/file2.txt".to_string(), b"content2".to_vec()),
         ];
-        let modified = now();
-        let zip_file = TempZipFile::new(&files, modified)?;
+        let zip_file = TempZipFile::new(&files, now())?;
         let current_dir = TempDir::new()?;
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
 
-        let output = make_plugin_with_pwd(current_dir.path())?
-            .eval(&format!("unzip {}", zip_file.path()))?
+        let output = plugin
+            .eval(&format!("unzip --spool {}", zip_file.path()))?
             .into_value(Span::test_data())?;
+        let dir = PathBuf::from(output.get_data_by_key("dir").unwrap().as_str()?);
+        assert!(dir.starts_with(spool_root()));
+        check_extracted_files(&files, &dir);
+        assert_eq!(
+            output.get_data_by_key("files").unwrap(),
+            Value::test_list(vec![
+                Value::test_string(dir.join("dir/file2.txt").to_string_lossy()),
+                Value::test_string(dir.join("file1.txt").to_string_lossy()),
+            ])
+        );
+        assert!(!current_dir.path().join("file1.txt").exists());
 
-        assert_eq!(output, Value::nothing(Span::test_data()));
+        Ok(())
+    }
 
-        check_extracted_files(&files, current_dir.path());
+    #[test]
+    fn test_unzip_latest_only() -> Result<()> {
+        let zip_file = TempZipFile::new(
+            &[
+                ("fileA.txt".to_string(), b"old".to_vec()),
+                ("fileB.txt".to_string(), b"new".to_vec()),
+            ],
+            now(),
+        )?;
+        let current_dir = TempDir::new()?;
+
+        // rename the second entry to the first, as if the archive was updated by appending
+        let mut bytes = fs::read(zip_file.path())?;
+        for i in 0..bytes.len() - 9 {
+            if &bytes[i..i + 9] == b"fileB.txt" {
+                bytes[i + 4] = b'A';
+            }
+        }
+        fs::write(zip_file.path(), bytes)?;
+
+        let filtered = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "(unzip --latest-only --report {}).filtered",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+        assert_eq!(filtered, Value::test_int(1));
+        check_extracted_files(
+            &[("fileA.txt".to_string(), b"new".to_vec())],
+            current_dir.path(),
+        );
+        assert!(!current_dir.path().join("fileB.txt").exists());
 
         Ok(())
     }
-    
+
     #[test]
-    fn test_unzip_force() -> Result<()> {
+    fn test_unzip_deflate64() -> Result<()> {
+        // few writers produce deflate64, so this one is committed
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deflate64.zip");
+        let current_dir = TempDir::new()?;
+
+        let mut plugin = make_plugin_with_pwd(current_dir.path())?;
+        let output = plugin
+            .eval(&format!("(unzip -l -v {}).compression", fixture.display()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_string("deflate64")])
+        );
+
+        plugin.eval(&format!("unzip {}", fixture.display()))?;
+        check_extracted_files(
+            &[(
+                "deflate64.txt".to_string(),
+                b"Enhanced deflate fixture, written as deflate64.\n".repeat(3),
+            )],
+            current_dir.path(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_header_consistent() -> Result<()> {
         let files = vec![
             ("file1.txt".to_string(), b"content1".to_vec()),
-            ("file2.txt".to_string(), b"hello content2".to_vec()),
+            ("file2.txt".to_string(), b"content2".to_vec()),
         ];
-        let modified = now();
-        let zip_file = TempZipFile::new(&files, modified)?;
-        let current_dir = TempDir::new()?;
+        let zip_file = TempZipFile::new(&files, now())?;
+
+        // tamper with the CRC32 in the local header of the first entry
+        let mut bytes = fs::read(zip_file.path())?;
+        bytes[14] ^= 0xff;
+        fs::write(zip_file.path(), bytes)?;
+
+        let output = make_plugin()?
+            .eval(&format!(
+                "(unzip -l -v -q {}).header_consistent",
+                zip_file.path()
+            ))?
+            .into_value(Span::test_data())?;
+
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_bool(false), Value::test_bool(true)])
+        );
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_unzip_recursive_zips() -> Result<()> {
+        let inner = TempZipFile::new(&[("deep.txt".to_string(), b"deep".to_vec())], now())?;
+        let middle =
+            TempZipFile::new(&[("inner.zip".to_string(), fs::read(inner.path())?)], now())?;
+        let outer = TempZipFile::new(
+            &[
+                ("top.txt".to_string(), b"top".to_vec()),
+                ("nested/middle.zip".to_string(), fs::read(middle.path())?),
+            ],
+            now(),
+        )?;
+        let current_dir = TempDir::new()?;
         let mut plugin = make_plugin_with_pwd(current_dir.path())?;
 
-        let cmd = format!("unzip {}", zip_file.path());
-        plugin.eval(&cmd)?;
+        let output = plugin
+            .eval(&format!("(unzip --recursive-zips {}).depth", outer.path()))?
+            .into_value(Span::test_data())?;
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_int(1), Value::test_int(2)])
+        );
+        let middle_dir = current_dir.path().join("nested/middle");
+        assert_eq!(fs::read(middle_dir.join("inner/deep.txt"))?, b"deep");
 
-        let res = plugin.eval(&cmd);
+        let other_dir = TempDir::new()?;
+        make_plugin_with_pwd(other_dir.path())?.eval(&format!(
+            "unzip -q --recursive-zips --max-depth 1 {}",
+            outer.path()
+        ))?;
+        assert!(other_dir.path().join("nested/middle/inner.zip").is_file());
+        assert!(!other_dir.path().join("nested/middle/inner").exists());
 
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().contains("already exists"));
+        Ok(())
+    }
 
-        plugin.eval(&(cmd + " -f"))?;
+    #[test]
+    fn test_unzip_recursive_zips_outer_options() -> Result<()> {
+        let inner = TempZipFile::new(&[("deep.txt".to_string(), b"deep".to_vec())], now())?;
+        let outer = TempZipFile::new(
+            &[
+                ("top.txt".to_string(), b"top".to_vec()),
+                ("bad.zip".to_string(), b"not a zip".to_vec()),
+                ("nested/middle.zip".to_string(), fs::read(inner.path())?),
+            ],
+            now(),
+        )?;
+
+        // the filter is for the names of the outer archive, not deep.txt
+        let current_dir = TempDir::new()?;
+        make_plugin_with_pwd(current_dir.path())?.eval(&format!(
+            "unzip -q --recursive-zips --regex 'middle' {}",
+            outer.path()
+        ))?;
+        assert_eq!(
+            fs::read(current_dir.path().join("nested/middle/deep.txt"))?,
+            b"deep"
+        );
+
+        // a corrupt nested zip is skipped, and a renamed one is unzipped under its new name
+        let current_dir = TempDir::new()?;
+        fs::create_dir(current_dir.path().join("nested"))?;
+        fs::write(current_dir.path().join("nested/middle.zip"), "existing")?;
+        let output = make_plugin_with_pwd(current_dir.path())?
+            .eval(&format!(
+                "(unzip -q --recursive-zips --rename-on-conflict {}).dir",
+                outer.path()
+            ))?
+            .into_value(Span::test_data())?;
+        let renamed = current_dir.path().join("nested/middle (1)");
+        assert_eq!(
+            output,
+            Value::test_list(vec![Value::test_string(renamed.to_string_lossy())])
+        );
+        assert_eq!(fs::read(renamed.join("deep.txt"))?, b"deep");
+        assert_eq!(fs::read(current_dir.path().join("top.txt"))?, b"top");
 
         Ok(())
     }
 
     #[test]
-    fn test_unzip_simple_zip_to_specified_dir() -> Result<()> {
+    fn test_unzip_events() -> Result<()> {
         let files = vec![
             ("file1.txt".to_string(), b"content1".to_vec()),
-            ("file2.txt".to_string(), b"hello content2".to_vec()),
+            ("../evil.txt".to_string(), b"evil".to_vec()),
         ];
-        let modified = now();
-        let zip_file = TempZipFile::new(&files, modified)?;
+        let zip_file = TempZipFile::new(&files, now())?;
         let current_dir = TempDir::new()?;
-        let dest_dir = TempDir::new()?;
 
         let output = make_plugin_with_pwd(current_dir.path())?
             .eval(&format!(
-                "unzip -d {} {}",
-                dest_dir.path().to_string_lossy(),
+                "let events = unzip -q --events {}; [$events.event $events.bytes]",
                 zip_file.path()
             ))?
             .into_value(Span::test_data())?;
 
-        assert_eq!(output, Value::nothing(Span::test_data()));
-
-        assert!(fs::read_dir(current_dir.path()).unwrap().next().is_none());
-        check_extracted_files(&files, dest_dir.path());
+        assert_eq!(
+            output,
+            Value::test_list(vec![
+                Value::test_list(vec![
+                    Value::test_string("start"),
+                    Value::test_string("done"),
+                    Value::test_string("skip"),
+                ]),
+                Value::test_list(vec![
+                    Value::test_filesize(0),
+                    Value::test_filesize(8),
+                    Value::test_filesize(0),
+                ]),
+            ])
+        );
+        check_extracted_files(&files[..1], current_dir.path());
 
         Ok(())
     }
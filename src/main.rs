@@ -1,6 +1,8 @@
 use nu_plugin::{serve_plugin, MsgPackSerializer};
-use nu_plugin_unzip::UnzipPlugin;
+use nu_plugin_unzip::{remove_spool, UnzipPlugin};
 
 fn main() {
-    serve_plugin(&UnzipPlugin {}, MsgPackSerializer {})
+    serve_plugin(&UnzipPlugin {}, MsgPackSerializer {});
+    // the plugin is done, so are the files it spooled
+    let _ = remove_spool();
 }